@@ -1,10 +1,21 @@
 use crate::{
     editor::EditorCommand,
-    ig::{Ig, SearchConfig},
+    ig::{
+        archive, decompress, patch_root, revision, FileChange, GitScope, Ig, Replacer,
+        SearchConfig, STDIN_NAME,
+    },
     ui::{
-        bottom_bar, cmd_parse::SearchCmd, context_viewer::ContextViewer,
-        input_handler::InputHandler, keymap_popup::KeymapPopup, result_list::ResultList,
-        search_popup::SearchPopup, theme::Theme,
+        bottom_bar,
+        cmd_parse::SearchCmd,
+        context_viewer::ContextViewer,
+        diagnostics_popup::DiagnosticsPopup,
+        input_handler::InputHandler,
+        keymap_popup::KeymapPopup,
+        replace_popup::ReplacePopup,
+        result_list::ResultList,
+        search_popup::SearchPopup,
+        sort::{Sort, SortKey},
+        theme::Theme,
     },
 };
 use anyhow::Result;
@@ -19,34 +30,61 @@ use ratatui::{
     layout::{Constraint, Direction, Layout},
     Frame, Terminal,
 };
-use std::path::PathBuf;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// File in the current directory to which replacements are exported.
+const PATCH_FILE: &str = "higrep.patch";
 
 pub struct App {
     search_config: SearchConfig,
+    replacer: Option<Replacer>,
+    /// Changes of files and errors of ones which cannot be changed, waiting for confirmation.
+    pending_changes: Option<(Vec<FileChange>, Vec<String>)>,
+    /// Revision whose changes are searched when cycling through git scopes.
+    git_diff_rev: String,
     ig: Ig,
     theme: Box<dyn Theme>,
     result_list: ResultList,
     context_viewer: ContextViewer,
     search_popup: SearchPopup,
+    replace_popup: ReplacePopup,
     keymap_popup: KeymapPopup,
+    diagnostics_popup: DiagnosticsPopup,
 }
 
 impl App {
     pub fn new(
         search_config: SearchConfig,
+        replacer: Option<Replacer>,
         editor_command: EditorCommand,
         context_viewer: ContextViewer,
         theme: Box<dyn Theme>,
+        sort: Option<Sort>,
     ) -> Self {
         let theme = theme;
+        let mut result_list = ResultList::default();
+        result_list.sort_by(sort);
+        let encoding = search_config.encoding;
+        let git_diff_rev = match &search_config.git_scope {
+            GitScope::Diff(rev) => rev.clone(),
+            _ => "HEAD".into(),
+        };
         Self {
             search_config,
-            ig: Ig::new(editor_command),
+            git_diff_rev,
+            replacer,
+            pending_changes: None,
+            ig: Ig::new(editor_command, encoding),
             theme,
             context_viewer,
-            result_list: ResultList::default(),
+            result_list,
             search_popup: SearchPopup::default(),
+            replace_popup: ReplacePopup::default(),
             keymap_popup: KeymapPopup::default(),
+            diagnostics_popup: DiagnosticsPopup::default(),
         }
     }
 
@@ -80,14 +118,23 @@ impl App {
 
                 input_handler.handle_input(self)?;
 
-                if let Some((file_name, _)) = self.result_list.get_selected_entry() {
-                    self.context_viewer
-                        .update_if_needed(&PathBuf::from(file_name), self.theme.as_ref());
+                if self.search_popup.take_due_search() {
+                    self.search_from_popup();
+                }
+
+                if let Some((file_name, line_number)) = self.result_list.get_selected_entry() {
+                    self.context_viewer.update_if_needed(
+                        PathBuf::from(file_name),
+                        line_number,
+                        self.theme.as_ref(),
+                    );
                 }
             }
 
-            self.ig
-                .open_file_if_requested(self.result_list.get_selected_entry());
+            self.ig.open_file_if_requested(
+                self.result_list.get_selected_entry(),
+                self.result_list.get_selected_column(),
+            );
 
             if self.ig.exit_requested() {
                 execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
@@ -112,7 +159,8 @@ impl App {
         let (view_area, bottom_bar_area) = (chunks[0], chunks[1]);
         let (list_area, context_viewer_area) = app.context_viewer.split_view(view_area);
 
-        app.result_list.draw(frame, list_area, app.theme.as_ref());
+        app.result_list
+            .draw(frame, list_area, app.theme.as_ref(), app.replacer.as_ref());
 
         if let Some(cv_area) = context_viewer_area {
             app.context_viewer
@@ -124,12 +172,115 @@ impl App {
             bottom_bar_area,
             &app.result_list,
             &app.ig,
+            &app.search_config,
             input_handler,
             app.theme.as_ref(),
         );
 
-        app.search_popup.draw(frame, app.theme.as_ref());
+        let live_summary = app
+            .search_popup
+            .is_incremental()
+            .then(|| app.live_search_summary());
+        app.search_popup
+            .draw(frame, live_summary, app.theme.as_ref());
+        app.replace_popup.draw(frame, app.theme.as_ref());
         app.keymap_popup.draw(frame, app.theme.as_ref());
+        app.diagnostics_popup
+            .draw(frame, app.ig.diagnostics(), app.theme.as_ref());
+    }
+}
+
+impl App {
+    /// Searches with the pattern from the popup. An invalid pattern is not searched for,
+    /// its error is shown in the popup and false is returned.
+    fn search_from_popup(&mut self) -> bool {
+        let mut search_config = self.search_config.clone();
+        let pattern = self.search_popup.get_pattern();
+        if let Some(cmd) = SearchCmd::parse(&pattern) {
+            search_config.update_from(cmd);
+        } else {
+            search_config.patterns = vec![pattern];
+        }
+        search_config.multiline = self.search_popup.is_multiline();
+        search_config.fixed_strings = self.search_popup.is_fixed_strings();
+        search_config.line_regexp = self.search_popup.is_line_regexp();
+        search_config.invert_match = self.search_popup.is_invert_match();
+        if let Err(error) = search_config.validate() {
+            self.search_popup.set_error(error.to_string());
+            return false;
+        }
+        self.search_config = search_config;
+        self.ig
+            .search(self.search_config.clone(), &mut self.result_list);
+        // the replacement is kept for the new pattern, if it can still be used
+        if let Some(replacer) = self.replacer.take() {
+            match Replacer::new(&self.search_config, replacer.template().into()) {
+                Ok(replacer) => self.replacer = Some(replacer),
+                Err(error) => self.ig.set_notice(format!("Replacement cleared: {error}.")),
+            }
+        }
+        true
+    }
+
+    /// Changes of files with matches left in the list, and errors of files that cannot
+    /// be changed. Files are read again, lines changed since the search are left as they are
+    /// and reported.
+    fn file_changes(&self, replacer: &Replacer) -> (Vec<FileChange>, Vec<String>) {
+        let mut changes = Vec::new();
+        let mut errors = Vec::new();
+        for (name, lines) in self.result_list.matched_lines() {
+            let path = Path::new(&name);
+            let unsupported = if name == STDIN_NAME {
+                Some("standard input cannot be changed")
+            } else if archive::split_member_path(&name).is_some() {
+                Some("archive members cannot be changed")
+            } else if self.search_config.revisions.is_some()
+                && revision::split_revision_path(&name).is_some()
+            {
+                Some("files in git history cannot be changed")
+            } else if decompress::is_compressed(path) {
+                Some("compressed files cannot be changed")
+            } else if self.search_config.preprocessor.applies_to(path) {
+                Some("preprocessed files cannot be changed")
+            } else {
+                None
+            };
+            if let Some(reason) = unsupported {
+                errors.push(format!("Cannot replace in '{name}': {reason}"));
+                continue;
+            }
+
+            match replacer.change_file(path, &lines) {
+                Ok(change) => {
+                    if !change.stale_lines().is_empty() {
+                        let numbers = change
+                            .stale_lines()
+                            .iter()
+                            .map(u64::to_string)
+                            .collect::<Vec<_>>();
+                        errors.push(format!(
+                            "Lines {} of '{name}' changed since the search, they are left as they are",
+                            numbers.join(", ")
+                        ));
+                    }
+                    if change.changed_lines() > 0 {
+                        changes.push(change);
+                    }
+                }
+                Err(error) => errors.push(format!("Cannot replace in '{name}': {error}")),
+            }
+        }
+        (changes, errors)
+    }
+
+    fn live_search_summary(&self) -> String {
+        if self.search_popup.has_error() {
+            return "invalid pattern".into();
+        }
+        let matches = self.result_list.get_total_number_of_matches();
+        let files = self.result_list.get_total_number_of_file_entries();
+        let searching = if self.ig.is_searching() { "…" } else { "" };
+        format!("{matches} matches in {files} files{searching}")
     }
 }
 
@@ -190,13 +341,139 @@ impl Application for App {
         self.ig.open_file();
     }
 
-    fn on_search(&mut self) {
-        let pattern = self.search_popup.get_pattern();
-        if let Some(cmd) = SearchCmd::parse(&pattern) {
-            self.search_config.update_from(cmd);
-        } else {
-            self.search_config.pattern = pattern;
+    fn on_search(&mut self) -> bool {
+        self.search_from_popup()
+    }
+
+    fn on_cancel_search(&mut self) {
+        self.ig.cancel_search();
+    }
+
+    fn on_request_replacements(&mut self) -> bool {
+        let Some(replacer) = &self.replacer else {
+            self.ig
+                .set_notice("No replacement, press r to set one.".into());
+            return false;
+        };
+        if self.ig.is_searching() {
+            self.ig
+                .set_notice("Replacing is possible once the search is finished.".into());
+            return false;
+        }
+
+        let (changes, errors) = self.file_changes(replacer);
+        if changes.is_empty() {
+            for error in errors {
+                self.ig.add_diagnostic(error);
+            }
+            self.ig.set_notice("Nothing to replace.".into());
+            return false;
+        }
+        let lines = changes.iter().map(FileChange::changed_lines).sum::<usize>();
+        self.ig.set_notice(format!(
+            "Replace {lines} lines in {} files? (y/n)",
+            changes.len()
+        ));
+        self.pending_changes = Some((changes, errors));
+        true
+    }
+
+    fn on_apply_replacements(&mut self) {
+        let Some((changes, mut errors)) = self.pending_changes.take() else {
+            return;
+        };
+        let (mut files, mut lines) = (0, 0);
+        for change in changes {
+            match change.apply() {
+                Ok(()) => {
+                    files += 1;
+                    lines += change.changed_lines();
+                }
+                Err(error) => errors.push(format!(
+                    "Cannot replace in '{}': {error}",
+                    change.path().display()
+                )),
+            }
+        }
+
+        // results are searched again to show the files as they are now
+        self.replacer = None;
+        self.ig
+            .search(self.search_config.clone(), &mut self.result_list);
+        for error in errors {
+            self.ig.add_diagnostic(error);
+        }
+        self.ig
+            .set_notice(format!("Replaced {lines} lines in {files} files."));
+    }
+
+    fn on_cancel_replacements(&mut self) {
+        self.pending_changes = None;
+        self.ig.set_notice("Replacing cancelled.".into());
+    }
+
+    fn on_export_patch(&mut self) {
+        let Some(replacer) = &self.replacer else {
+            self.ig
+                .set_notice("No replacement, press r to set one.".into());
+            return;
+        };
+
+        let (changes, errors) = self.file_changes(replacer);
+        for error in errors {
+            self.ig.add_diagnostic(error);
+        }
+        if changes.is_empty() {
+            self.ig.set_notice("Nothing to export.".into());
+            return;
+        }
+
+        let root = match patch_root() {
+            Ok(root) => root,
+            Err(error) => {
+                self.ig
+                    .add_diagnostic(format!("Cannot export {PATCH_FILE}: {error}"));
+                return;
+            }
+        };
+        let patch = changes
+            .iter()
+            .map(|change| change.unified_diff(&root))
+            .collect::<String>();
+        let lines = changes.iter().map(FileChange::changed_lines).sum::<usize>();
+        match fs::write(PATCH_FILE, patch) {
+            Ok(()) => self.ig.set_notice(format!(
+                "Exported {lines} lines in {} files to {PATCH_FILE}.",
+                changes.len()
+            )),
+            Err(error) => self
+                .ig
+                .add_diagnostic(format!("Cannot write {PATCH_FILE}: {error}")),
+        }
+    }
+
+    fn on_cycle_sort(&mut self) {
+        let current = self.result_list.sort();
+        let reverse = current.is_some_and(|sort| sort.reverse);
+        let sort = SortKey::next(current.map(|sort| sort.key)).map(|key| Sort::new(key, reverse));
+        self.result_list.sort_by(sort);
+    }
+
+    fn on_toggle_sort_order(&mut self) {
+        let sort = match self.result_list.sort() {
+            Some(sort) => Sort::new(sort.key, !sort.reverse),
+            None => Sort::new(SortKey::Path, true),
+        };
+        self.result_list.sort_by(Some(sort));
+    }
+
+    fn on_cycle_git_scope(&mut self) {
+        if self.search_config.revisions.is_some() {
+            self.ig
+                .set_notice("Git scopes do not apply to searched history.".into());
+            return;
         }
+        self.search_config.git_scope = self.search_config.git_scope.next(&self.git_diff_rev);
         self.ig
             .search(self.search_config.clone(), &mut self.result_list);
     }
@@ -207,10 +484,54 @@ impl Application for App {
 
     fn on_toggle_popup(&mut self) {
         self.search_popup
-            .set_pattern(self.search_config.pattern.clone());
+            .set_pattern(self.search_config.to_cmd().to_string());
+        self.search_popup
+            .set_multiline(self.search_config.multiline);
+        self.search_popup
+            .set_fixed_strings(self.search_config.fixed_strings);
+        self.search_popup
+            .set_line_regexp(self.search_config.line_regexp);
+        self.search_popup
+            .set_invert_match(self.search_config.invert_match);
         self.search_popup.toggle();
     }
 
+    fn on_toggle_replace_popup(&mut self) {
+        self.replace_popup.set_template(
+            self.replacer
+                .as_ref()
+                .map(|replacer| replacer.template().to_owned())
+                .unwrap_or_default(),
+        );
+        self.replace_popup.toggle();
+    }
+
+    fn on_replace_char_inserted(&mut self, c: char) {
+        self.replace_popup.insert_char(c);
+    }
+
+    fn on_replace_char_removed(&mut self) {
+        self.replace_popup.remove_char();
+    }
+
+    fn on_set_replacement(&mut self) -> bool {
+        match Replacer::new(&self.search_config, self.replace_popup.get_template()) {
+            Ok(replacer) => {
+                self.replacer = Some(replacer);
+                true
+            }
+            Err(error) => {
+                self.replace_popup.set_error(error.to_string());
+                false
+            }
+        }
+    }
+
+    fn on_clear_replacement(&mut self) {
+        self.replacer = None;
+        self.replace_popup.toggle();
+    }
+
     fn on_char_inserted(&mut self, c: char) {
         self.search_popup.insert_char(c);
     }
@@ -219,6 +540,26 @@ impl Application for App {
         self.search_popup.remove_char();
     }
 
+    fn on_toggle_multiline(&mut self) {
+        self.search_popup.toggle_multiline();
+    }
+
+    fn on_toggle_fixed_strings(&mut self) {
+        self.search_popup.toggle_fixed_strings();
+    }
+
+    fn on_toggle_line_regexp(&mut self) {
+        self.search_popup.toggle_line_regexp();
+    }
+
+    fn on_toggle_invert_match(&mut self) {
+        self.search_popup.toggle_invert_match();
+    }
+
+    fn on_toggle_incremental(&mut self) {
+        self.search_popup.toggle_incremental();
+    }
+
     fn on_toggle_keymap(&mut self) {
         self.keymap_popup.toggle();
     }
@@ -239,6 +580,18 @@ impl Application for App {
         self.keymap_popup.go_right();
     }
 
+    fn on_toggle_diagnostics(&mut self) {
+        self.diagnostics_popup.toggle();
+    }
+
+    fn on_diagnostics_up(&mut self) {
+        self.diagnostics_popup.go_up();
+    }
+
+    fn on_diagnostics_down(&mut self) {
+        self.diagnostics_popup.go_down();
+    }
+
     fn on_text_wrapper(&mut self) {
         self.result_list.toggel_text_wrapper();
     }
@@ -271,14 +624,38 @@ pub trait Application {
     fn on_increase_context_viewer_size(&mut self);
     fn on_decrease_context_viewer_size(&mut self);
     fn on_open_file(&mut self);
-    fn on_search(&mut self);
+    /// Returns false if the pattern is invalid, the popup stays open then.
+    fn on_search(&mut self) -> bool;
+    fn on_cancel_search(&mut self);
+    /// Returns true if there are changes to be confirmed before they are written.
+    fn on_request_replacements(&mut self) -> bool;
+    fn on_apply_replacements(&mut self);
+    fn on_cancel_replacements(&mut self);
+    fn on_export_patch(&mut self);
+    fn on_cycle_sort(&mut self);
+    fn on_toggle_sort_order(&mut self);
+    fn on_cycle_git_scope(&mut self);
     fn on_exit(&mut self);
     fn on_toggle_popup(&mut self);
     fn on_char_inserted(&mut self, c: char);
     fn on_char_removed(&mut self);
+    fn on_toggle_replace_popup(&mut self);
+    fn on_replace_char_inserted(&mut self, c: char);
+    fn on_replace_char_removed(&mut self);
+    /// Returns false if matches cannot be replaced, the popup stays open then.
+    fn on_set_replacement(&mut self) -> bool;
+    fn on_clear_replacement(&mut self);
+    fn on_toggle_multiline(&mut self);
+    fn on_toggle_fixed_strings(&mut self);
+    fn on_toggle_line_regexp(&mut self);
+    fn on_toggle_invert_match(&mut self);
+    fn on_toggle_incremental(&mut self);
     fn on_toggle_keymap(&mut self);
     fn on_keymap_up(&mut self);
     fn on_keymap_down(&mut self);
     fn on_keymap_left(&mut self);
     fn on_keymap_right(&mut self);
+    fn on_toggle_diagnostics(&mut self);
+    fn on_diagnostics_up(&mut self);
+    fn on_diagnostics_down(&mut self);
 }
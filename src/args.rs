@@ -1,6 +1,12 @@
 use crate::{
     editor::Editor,
-    ui::{context_viewer::ContextViewerPosition, theme::ThemeVariant},
+    ig::{revision::Revisions, Engine, FileEncoding, GitScope},
+    ui::{
+        cmd_parse::parse_filesize,
+        context_viewer::ContextViewerPosition,
+        sort::{Sort, SortKey},
+        theme::ThemeVariant,
+    },
 };
 use clap::{ArgGroup, CommandFactory, Parser};
 use std::{
@@ -21,20 +27,39 @@ pub const VISUAL_ENV: &str = "VISUAL";
 #[clap(author, version, about, long_about = None)]
 #[clap(group(
             ArgGroup::new("pattern_or_type_list")
-                .args(&["pattern", "type-list"])
+                .args(&["pattern", "type-list", "regexp", "pattern-files"])
                 .required(true)
+                .multiple(true)
 ))]
 pub struct Args {
     /// Regular expression used for searching.
+    /// Treated as the first path if patterns are provided with --regexp or --file.
     pub pattern: Option<String>,
-    /// Files or directories to search. Directories are searched recursively.
-    /// If not specified, searching starts from current directory.
+    /// Files or directories to search. Directories are searched recursively, `-` is standard input.
+    /// If not specified, piped standard input is searched, otherwise searching starts from
+    /// current directory.
     pub paths: Vec<PathBuf>,
+    /// A pattern to search for. Multiple patterns may be provided,
+    /// each one is highlighted with a different color.
+    #[clap(short = 'e', long, value_name = "PATTERN")]
+    pub regexp: Vec<String>,
+    /// Search for patterns from the given file, one pattern per line.
+    /// Multiple files may be provided.
+    #[clap(short = 'f', long = "file", value_name = "PATTERNFILE")]
+    pub pattern_files: Vec<PathBuf>,
     #[clap(flatten)]
     pub editor: EditorOpt,
     /// UI color theme.
     #[clap(long, arg_enum, default_value_t = ThemeVariant::Dark)]
     pub theme: ThemeVariant,
+    /// Regex engine used for searching. PCRE2 supports lookaround and backreferences
+    /// and requires higrep to be built with the `pcre2` feature.
+    #[clap(long, arg_enum, default_value_t = Engine::Default)]
+    pub engine: Engine,
+    /// Text encoding of searched files, e.g. gbk, shift_jis or utf-16le.
+    /// `auto` uses the BOM if present and guesses the encoding of files that are not valid UTF-8.
+    #[clap(short = 'E', long, value_name = "ENCODING", default_value_t = FileEncoding::Auto)]
+    pub encoding: FileEncoding,
     /// Searches case insensitively.
     #[clap(short = 'i', long)]
     pub ignore_case: bool,
@@ -52,6 +77,102 @@ pub struct Args {
     /// Only show matches surrounded by word boundaries.
     #[clap(short = 'w', long = "word-regexp")]
     pub word_regexp: bool,
+    /// Treat the pattern as a literal string instead of a regular expression.
+    #[clap(short = 'F', long)]
+    pub fixed_strings: bool,
+    /// Only show matches surrounded by line boundaries.
+    #[clap(short = 'x', long = "line-regexp")]
+    pub line_regexp: bool,
+    /// Show lines that do not match the given pattern.
+    #[clap(short = 'v', long)]
+    pub invert_match: bool,
+    /// Preview replacing every match with REPLACEMENT, which may refer to capture groups
+    /// as `$1` or `${name}`. Replacements are written to files with `R` or exported as
+    /// a patch with `P`.
+    #[clap(short = 'r', long, value_name = "REPLACEMENT")]
+    pub replace: Option<String>,
+    /// Search binary files and report matches in them without showing their content.
+    /// By default, files containing NUL bytes are skipped.
+    #[clap(long, conflicts_with = "text")]
+    pub binary: bool,
+    /// Search binary files as if they were text.
+    #[clap(short = 'a', long)]
+    pub text: bool,
+    /// Search in compressed files (gzip, bzip2, xz, lz4, lzma, brotli, zstd and compress)
+    /// using the corresponding external decompression tool.
+    #[clap(short = 'z', long)]
+    pub search_zip: bool,
+    /// Search files inside tar and zip archives (also jar, war and ear), listed as
+    /// `archive!/member`. Compressed tar archives are decompressed like with --search-zip.
+    /// --max-filesize applies to each member, --pre is not run for archives or their members.
+    #[clap(long)]
+    pub archives: bool,
+    /// For each file, run COMMAND with the file path as its argument and the file contents
+    /// on stdin, and search its output instead, e.g. to search PDF or docx files.
+    #[clap(long, value_name = "COMMAND")]
+    pub pre: Option<PathBuf>,
+    /// Only run the --pre command on files matching GLOB. Multiple globs may be provided.
+    #[clap(long, value_name = "GLOB", requires = "pre")]
+    pub pre_glob: Vec<String>,
+    /// Descend at most NUM directories below the given paths.
+    #[clap(long, value_name = "NUM")]
+    pub max_depth: Option<usize>,
+    /// Ignore files larger than NUM in size. Accepts K, M and G suffixes.
+    #[clap(long, value_name = "NUM+SUFFIX?", value_parser = parse_filesize)]
+    pub max_filesize: Option<u64>,
+    /// Show at most NUM matches per file.
+    #[clap(short = 'm', long, value_name = "NUM")]
+    pub max_count: Option<u64>,
+    /// Shorten lines longer than NUM bytes, e.g. of minified files, to a note that they are
+    /// omitted. The context viewer still shows whole lines.
+    #[clap(short = 'M', long, value_name = "NUM")]
+    pub max_columns: Option<usize>,
+    /// Show a part of lines longer than --max-columns around their first match, with ellipses
+    /// where they are cut, instead of omitting them.
+    #[clap(long, requires = "max-columns")]
+    pub max_columns_preview: bool,
+    /// Only search files tracked by git.
+    #[clap(long, conflicts_with_all = &["git-changed", "git-diff"])]
+    pub git_tracked: bool,
+    /// Only search files changed in the working tree or the index against HEAD.
+    #[clap(long, conflicts_with = "git-diff")]
+    pub git_changed: bool,
+    /// Only show matches on lines changed in the working tree against REV.
+    #[clap(long, value_name = "REV")]
+    pub git_diff: Option<String>,
+    /// Search files as they were in COMMIT, read from the git history instead of the working tree.
+    #[clap(
+        long,
+        value_name = "COMMIT",
+        conflicts_with_all = &["log-range", "git-tracked", "git-changed", "git-diff"]
+    )]
+    pub rev: Option<String>,
+    /// Search files changed by each commit of RANGE, e.g. main..feature, as they were after it.
+    #[clap(
+        long,
+        value_name = "RANGE",
+        conflicts_with_all = &["git-tracked", "git-changed", "git-diff"]
+    )]
+    pub log_range: Option<String>,
+    /// Sort files in ascending order by the given key.
+    #[clap(long, arg_enum, value_name = "SORTBY", conflicts_with = "sortr")]
+    pub sort: Option<SortKey>,
+    /// Sort files in descending order by the given key.
+    #[clap(long, arg_enum, value_name = "SORTBY")]
+    pub sortr: Option<SortKey>,
+    /// Enable matching across multiple lines.
+    #[clap(short = 'U', long)]
+    pub multiline: bool,
+    /// Show NUM lines after each match.
+    #[clap(short = 'A', long, value_name = "NUM")]
+    pub after_context: Option<usize>,
+    /// Show NUM lines before each match.
+    #[clap(short = 'B', long, value_name = "NUM")]
+    pub before_context: Option<usize>,
+    /// Show NUM lines before and after each match.
+    /// Overridden by --after-context and --before-context.
+    #[clap(short = 'C', long, value_name = "NUM")]
+    pub context: Option<usize>,
     /// Include files and directories for searching that match the given glob.
     /// Multiple globs may be provided.
     #[clap(short, long)]
@@ -80,12 +201,64 @@ pub struct EditorOpt {
     #[clap(long, arg_enum)]
     pub editor: Option<Editor>,
 
-    /// Custom command used to open selected match. Must contain {file_name} and {line_number} tokens.
+    /// Custom command used to open selected match. Must contain {file_name} and {line_number} tokens
+    /// and may contain {column_number}.
     #[clap(long, env = IGREP_CUSTOM_EDITOR_ENV)]
     pub custom_command: Option<String>,
 }
 
 impl Args {
+    pub fn sort(&self) -> Option<Sort> {
+        match (self.sort, self.sortr) {
+            (Some(key), _) => Some(Sort::new(key, false)),
+            (None, Some(key)) => Some(Sort::new(key, true)),
+            (None, None) => None,
+        }
+    }
+
+    pub fn git_scope(&self) -> GitScope {
+        match (self.git_tracked, self.git_changed, &self.git_diff) {
+            (true, ..) => GitScope::Tracked,
+            (_, true, _) => GitScope::Changed,
+            (.., Some(rev)) => GitScope::Diff(rev.clone()),
+            _ => GitScope::All,
+        }
+    }
+
+    pub fn revisions(&self) -> Option<Revisions> {
+        match (&self.rev, &self.log_range) {
+            (Some(commit), _) => Some(Revisions::Commit(commit.clone())),
+            (None, Some(range)) => Some(Revisions::LogRange(range.clone())),
+            (None, None) => None,
+        }
+    }
+
+    /// Collects patterns given with --regexp and --file, falling back to the positional pattern.
+    /// Returns patterns and paths to search.
+    pub fn patterns_and_paths(&self) -> io::Result<(Vec<String>, Vec<PathBuf>)> {
+        let mut patterns = self.regexp.clone();
+        for pattern_file in &self.pattern_files {
+            let file = BufReader::new(File::open(pattern_file)?);
+            for line in file.lines() {
+                let line = line?;
+                if !line.is_empty() {
+                    patterns.push(line);
+                }
+            }
+        }
+
+        let mut paths = self.paths.clone();
+        match &self.pattern {
+            Some(pattern) if patterns.is_empty() && self.pattern_files.is_empty() => {
+                patterns.push(pattern.clone())
+            }
+            Some(path) => paths.insert(0, path.into()),
+            None => (),
+        }
+
+        Ok((patterns, paths))
+    }
+
     pub fn parse_cli_and_config_file() -> Self {
         // first validate if CLI arguments are valid
         Args::parse_from(std::env::args_os());
@@ -387,7 +560,7 @@ mod tests {
                 "ddd".to_owned(),
                 "e".to_owned(),
             ],
-            &vec![
+            &[
                 (Some("aaa".to_owned()), Some("a".to_owned()), false),
                 (Some("bbb".to_owned()), Some("b".to_owned()), false),
                 (Some("ccc".to_owned()), Some("c".to_owned()), false),
@@ -425,4 +598,76 @@ mod tests {
 
         assert_eq!(extended, expected);
     }
+
+    #[test]
+    fn positional_pattern_is_path_when_regexp_given() {
+        let args = Args::parse_from(["ig", "-e", "foo", "--regexp", "bar", "src", "tests"]);
+        let (patterns, paths) = args.patterns_and_paths().unwrap();
+        assert_eq!(patterns, ["foo", "bar"]);
+        assert_eq!(paths, [PathBuf::from("src"), PathBuf::from("tests")]);
+
+        let args = Args::parse_from(["ig", "foo", "src"]);
+        let (patterns, paths) = args.patterns_and_paths().unwrap();
+        assert_eq!(patterns, ["foo"]);
+        assert_eq!(paths, [PathBuf::from("src")]);
+    }
+
+    #[test]
+    fn limits() {
+        let args = Args::parse_from([
+            "ig",
+            "-m",
+            "3",
+            "--max-depth",
+            "2",
+            "--max-filesize",
+            "5K",
+            "foo",
+        ]);
+        assert_eq!(args.max_count, Some(3));
+        assert_eq!(args.max_depth, Some(2));
+        assert_eq!(args.max_filesize, Some(5 * 1024));
+
+        assert!(Args::try_parse_from(["ig", "--max-filesize", "5T", "foo"]).is_err());
+    }
+
+    #[test]
+    fn sort() {
+        let args = Args::parse_from(["ig", "--sort", "modified", "foo"]);
+        assert_eq!(args.sort(), Some(Sort::new(SortKey::Modified, false)));
+
+        let args = Args::parse_from(["ig", "--sortr", "matches", "foo"]);
+        assert_eq!(args.sort(), Some(Sort::new(SortKey::Matches, true)));
+
+        assert_eq!(Args::parse_from(["ig", "foo"]).sort(), None);
+        assert!(Args::try_parse_from(["ig", "--sort", "path", "--sortr", "path", "foo"]).is_err());
+    }
+
+    #[test]
+    fn git_scope() {
+        let args = Args::parse_from(["ig", "--git-changed", "foo"]);
+        assert_eq!(args.git_scope(), GitScope::Changed);
+
+        let args = Args::parse_from(["ig", "--git-diff", "main", "foo"]);
+        assert_eq!(args.git_scope(), GitScope::Diff("main".into()));
+
+        assert_eq!(Args::parse_from(["ig", "foo"]).git_scope(), GitScope::All);
+        assert!(Args::try_parse_from(["ig", "--git-tracked", "--git-changed", "foo"]).is_err());
+    }
+
+    #[test]
+    fn revisions() {
+        let args = Args::parse_from(["ig", "--rev", "HEAD~2", "foo"]);
+        assert_eq!(args.revisions(), Some(Revisions::Commit("HEAD~2".into())));
+
+        let args = Args::parse_from(["ig", "--log-range", "main..feature", "foo"]);
+        assert_eq!(
+            args.revisions(),
+            Some(Revisions::LogRange("main..feature".into()))
+        );
+
+        assert_eq!(Args::parse_from(["ig", "foo"]).revisions(), None);
+        assert!(Args::try_parse_from(["ig", "--rev", "a", "--log-range", "a..b", "foo"]).is_err());
+        assert!(Args::try_parse_from(["ig", "--rev", "a", "--git-changed", "foo"]).is_err());
+    }
 }
@@ -4,10 +4,15 @@ use clap::ArgEnum;
 use itertools::Itertools;
 use std::{
     fmt::{self, Debug, Display, Formatter},
-    io,
+    fs::File,
+    io::{self, IsTerminal},
     process::{Child, Command},
 };
 use strum_macros::Display;
+use unicode_width::UnicodeWidthChar;
+
+/// Width of a tab in display columns, as in the default setup of terminal editors.
+const TAB_WIDTH: usize = 8;
 
 #[derive(Display, Default, PartialEq, Eq, Copy, Clone, Debug, ArgEnum)]
 #[strum(serialize_all = "lowercase")]
@@ -33,6 +38,47 @@ pub enum Editor {
     Less,
 }
 
+/// Column of a match, counted in bytes, characters and display cells, each starting at 1.
+/// Editors differ in which one they expect.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Column {
+    /// `None` for files not in UTF-8, whose bytes differ from the decoded line it was counted in.
+    pub byte: Option<usize>,
+    pub char: usize,
+    pub display: usize,
+}
+
+impl Column {
+    /// Column of the given byte offset in a text, counted from the start of its line.
+    pub fn new(text: &str, offset: usize) -> Self {
+        let offset = (0..=offset.min(text.len()))
+            .rev()
+            .find(|&offset| text.is_char_boundary(offset))
+            .unwrap_or_default();
+        let line = &text[text[..offset].rfind('\n').map_or(0, |newline| newline + 1)..offset];
+        let display = line.chars().fold(0, |width, c| match c {
+            '\t' => (width / TAB_WIDTH + 1) * TAB_WIDTH,
+            c => width + c.width().unwrap_or_default(),
+        });
+
+        Self {
+            byte: Some(line.len() + 1),
+            char: line.chars().count() + 1,
+            display: display + 1,
+        }
+    }
+}
+
+impl Default for Column {
+    fn default() -> Self {
+        Self {
+            byte: Some(1),
+            char: 1,
+            display: 1,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum EditorCommand {
     Builtin(Editor),
@@ -57,6 +103,13 @@ impl EditorCommand {
                     .context(format!("Incorrect editor command: '{custom_command}'")));
             }
 
+            if args.matches("{column_number}").count() > 1 {
+                return Err(
+                    anyhow!("Expected at most one occurence of '{{column_number}}'.")
+                        .context(format!("Incorrect editor command: '{custom_command}'")),
+                );
+            }
+
             return Ok(EditorCommand::Custom(program.into(), args.into()));
         }
 
@@ -87,9 +140,15 @@ impl EditorCommand {
         ))
     }
 
-    pub fn spawn(&self, file_name: &str, line_number: u64) -> io::Result<Child> {
+    pub fn spawn(&self, file_name: &str, line_number: u64, column: Column) -> io::Result<Child> {
         let mut command = Command::new(self.program());
-        command.args(self.args(file_name, line_number));
+        command.args(self.args(file_name, line_number, column));
+        // standard input may be piped to the app, editor needs the terminal instead
+        if !io::stdin().is_terminal() {
+            if let Ok(tty) = File::open("/dev/tty") {
+                command.stdin(tty);
+            }
+        }
         command.spawn()
     }
 
@@ -116,33 +175,67 @@ impl EditorCommand {
         }
     }
 
-    fn args(&self, file_name: &str, line_number: u64) -> Box<dyn Iterator<Item = String>> {
+    fn args(
+        &self,
+        file_name: &str,
+        line_number: u64,
+        column: Column,
+    ) -> Box<dyn Iterator<Item = String>> {
         match self {
             EditorCommand::Builtin(editor) => match editor {
-                Editor::Vim
-                | Editor::Neovim
-                | Editor::Nvim
-                | Editor::Nano
-                | Editor::Micro
-                | Editor::Less => {
-                    Box::new([format!("+{line_number}"), file_name.into()].into_iter())
-                }
-                Editor::Code | Editor::Vscode | Editor::CodeInsiders => {
-                    Box::new(["-g".into(), format!("{file_name}:{line_number}")].into_iter())
-                }
+                // vim counts columns in bytes of the file, without them it moves to a column
+                // as displayed, which does not depend on the encoding
+                Editor::Vim | Editor::Neovim | Editor::Nvim => Box::new(
+                    [
+                        match column.byte {
+                            Some(byte) => format!("+call cursor({line_number},{byte})"),
+                            None => format!("+normal! {line_number}G{}|", column.display),
+                        },
+                        file_name.into(),
+                    ]
+                    .into_iter(),
+                ),
+                Editor::Nano => Box::new(
+                    [format!("+{line_number},{}", column.char), file_name.into()].into_iter(),
+                ),
+                Editor::Micro => Box::new(
+                    [format!("+{line_number}:{}", column.char), file_name.into()].into_iter(),
+                ),
+                Editor::Less => Box::new([format!("+{line_number}"), file_name.into()].into_iter()),
+                Editor::Code | Editor::Vscode | Editor::CodeInsiders => Box::new(
+                    [
+                        "-g".into(),
+                        format!("{file_name}:{line_number}:{}", column.char),
+                    ]
+                    .into_iter(),
+                ),
+                // emacs moves to a column as displayed, with tabs expanded
                 Editor::Emacs | Editor::Emacsclient => Box::new(
-                    ["-nw".into(), format!("+{line_number}"), file_name.into()].into_iter(),
+                    [
+                        "-nw".into(),
+                        format!("+{line_number}:{}", column.display),
+                        file_name.into(),
+                    ]
+                    .into_iter(),
                 ),
                 Editor::Hx | Editor::Helix | Editor::Subl | Editor::SublimeText => {
-                    Box::new([format!("{file_name}:{line_number}")].into_iter())
+                    Box::new([format!("{file_name}:{line_number}:{}", column.char)].into_iter())
                 }
                 Editor::Intellij | Editor::Goland | Editor::Pycharm => Box::new(
-                    ["--line".into(), format!("{line_number}"), file_name.into()].into_iter(),
+                    [
+                        "--line".into(),
+                        format!("{line_number}"),
+                        "--column".into(),
+                        format!("{}", column.char),
+                        file_name.into(),
+                    ]
+                    .into_iter(),
                 ),
             },
             EditorCommand::Custom(_, args) => {
                 let args = args.replace("{file_name}", file_name);
                 let args = args.replace("{line_number}", &line_number.to_string());
+                let args = args.replace("{column_number}", &column.char.to_string());
 
                 let args = args.split_whitespace().map(ToOwned::to_owned).collect_vec();
                 Box::new(args.into_iter())
@@ -183,6 +276,8 @@ mod tests {
     #[test_case("non_builtin_editor{file_name} {line_number}" => matches Err(_); "program not separated from arg")]
     #[test_case("non_builtin_editor {file_name}:{line_number}" => matches Ok(_); "correct command with one arg")]
     #[test_case("non_builtin_editor {file_name} {line_number}" => matches Ok(_); "correct command with two args")]
+    #[test_case("non_builtin_editor {file_name}:{line_number}:{column_number}" => matches Ok(_); "correct command with column")]
+    #[test_case("non_builtin_editor {file_name} {line_number} {column_number} {column_number}" => matches Err(_); "column number twice")]
     fn parsing_custom_command(command: &str) -> Result<EditorCommand> {
         EditorCommand::new(Some(command.into()), None)
     }
@@ -231,46 +326,87 @@ mod tests {
 
     const FILE_NAME: &str = "file_name";
     const LINE_NUMBER: u64 = 123;
+    const COLUMN: Column = Column {
+        byte: Some(7),
+        char: 5,
+        display: 10,
+    };
 
     #[test]
     fn custom_command() {
         let editor_command = EditorCommand::new(
-            Some("non_builtin_editor -@{file_name} {line_number}".into()),
+            Some("non_builtin_editor -@{file_name} {line_number} -c{column_number}".into()),
             None,
         )
         .unwrap();
 
         assert_eq!(editor_command.program(), "non_builtin_editor");
         assert_eq!(
-            editor_command.args(FILE_NAME, LINE_NUMBER).collect_vec(),
-            vec![format!("-@{FILE_NAME}"), LINE_NUMBER.to_string()]
+            editor_command
+                .args(FILE_NAME, LINE_NUMBER, COLUMN)
+                .collect_vec(),
+            vec![
+                format!("-@{FILE_NAME}"),
+                LINE_NUMBER.to_string(),
+                format!("-c{}", COLUMN.char)
+            ]
         )
     }
 
-    #[test_case(Editor::Vim => format!("vim +{LINE_NUMBER} {FILE_NAME}"); "vim command")]
-    #[test_case(Editor::Neovim => format!("nvim +{LINE_NUMBER} {FILE_NAME}"); "neovim command")]
-    #[test_case(Editor::Nvim => format!("nvim +{LINE_NUMBER} {FILE_NAME}"); "nvim command")]
-    #[test_case(Editor::Nano => format!("nano +{LINE_NUMBER} {FILE_NAME}"); "nano command")]
-    #[test_case(Editor::Code => format!("code -g {FILE_NAME}:{LINE_NUMBER}"); "code command")]
-    #[test_case(Editor::Vscode => format!("code -g {FILE_NAME}:{LINE_NUMBER}"); "vscode command")]
-    #[test_case(Editor::CodeInsiders => format!("code-insiders -g {FILE_NAME}:{LINE_NUMBER}"); "code-insiders command")]
-    #[test_case(Editor::Emacs => format!("emacs -nw +{LINE_NUMBER} {FILE_NAME}"); "emacs command")]
-    #[test_case(Editor::Emacsclient => format!("emacsclient -nw +{LINE_NUMBER} {FILE_NAME}"); "emacsclient command")]
-    #[test_case(Editor::Hx => format!("hx {FILE_NAME}:{LINE_NUMBER}"); "hx command")]
-    #[test_case(Editor::Helix => format!("helix {FILE_NAME}:{LINE_NUMBER}"); "helix command")]
-    #[test_case(Editor::Subl => format!("subl {FILE_NAME}:{LINE_NUMBER}"); "subl command")]
-    #[test_case(Editor::SublimeText => format!("subl {FILE_NAME}:{LINE_NUMBER}"); "sublime text command")]
-    #[test_case(Editor::Micro => format!("micro +{LINE_NUMBER} {FILE_NAME}"); "micro command")]
-    #[test_case(Editor::Intellij => format!("idea --line {LINE_NUMBER} {FILE_NAME}"); "intellij command")]
-    #[test_case(Editor::Goland => format!("goland --line {LINE_NUMBER} {FILE_NAME}"); "goland command")]
-    #[test_case(Editor::Pycharm => format!("pycharm --line {LINE_NUMBER} {FILE_NAME}"); "pycharm command")]
+    #[test_case(Editor::Vim => format!("vim +call cursor({LINE_NUMBER},7) {FILE_NAME}"); "vim command")]
+    #[test_case(Editor::Neovim => format!("nvim +call cursor({LINE_NUMBER},7) {FILE_NAME}"); "neovim command")]
+    #[test_case(Editor::Nvim => format!("nvim +call cursor({LINE_NUMBER},7) {FILE_NAME}"); "nvim command")]
+    #[test_case(Editor::Nano => format!("nano +{LINE_NUMBER},5 {FILE_NAME}"); "nano command")]
+    #[test_case(Editor::Code => format!("code -g {FILE_NAME}:{LINE_NUMBER}:5"); "code command")]
+    #[test_case(Editor::Vscode => format!("code -g {FILE_NAME}:{LINE_NUMBER}:5"); "vscode command")]
+    #[test_case(Editor::CodeInsiders => format!("code-insiders -g {FILE_NAME}:{LINE_NUMBER}:5"); "code-insiders command")]
+    #[test_case(Editor::Emacs => format!("emacs -nw +{LINE_NUMBER}:10 {FILE_NAME}"); "emacs command")]
+    #[test_case(Editor::Emacsclient => format!("emacsclient -nw +{LINE_NUMBER}:10 {FILE_NAME}"); "emacsclient command")]
+    #[test_case(Editor::Hx => format!("hx {FILE_NAME}:{LINE_NUMBER}:5"); "hx command")]
+    #[test_case(Editor::Helix => format!("helix {FILE_NAME}:{LINE_NUMBER}:5"); "helix command")]
+    #[test_case(Editor::Subl => format!("subl {FILE_NAME}:{LINE_NUMBER}:5"); "subl command")]
+    #[test_case(Editor::SublimeText => format!("subl {FILE_NAME}:{LINE_NUMBER}:5"); "sublime text command")]
+    #[test_case(Editor::Micro => format!("micro +{LINE_NUMBER}:5 {FILE_NAME}"); "micro command")]
+    #[test_case(Editor::Intellij => format!("idea --line {LINE_NUMBER} --column 5 {FILE_NAME}"); "intellij command")]
+    #[test_case(Editor::Goland => format!("goland --line {LINE_NUMBER} --column 5 {FILE_NAME}"); "goland command")]
+    #[test_case(Editor::Pycharm => format!("pycharm --line {LINE_NUMBER} --column 5 {FILE_NAME}"); "pycharm command")]
     #[test_case(Editor::Less => format!("less +{LINE_NUMBER} {FILE_NAME}"); "less command")]
     fn builtin_editor_command(editor: Editor) -> String {
         let editor_command = EditorCommand::new(None, Some(editor)).unwrap();
         format!(
             "{} {}",
             editor_command.program(),
-            editor_command.args(FILE_NAME, LINE_NUMBER).join(" ")
+            editor_command
+                .args(FILE_NAME, LINE_NUMBER, COLUMN)
+                .join(" ")
+        )
+    }
+
+    #[test_case(Editor::Vim => format!("vim +normal! {LINE_NUMBER}G10| {FILE_NAME}"); "vim command")]
+    #[test_case(Editor::Nvim => format!("nvim +normal! {LINE_NUMBER}G10| {FILE_NAME}"); "nvim command")]
+    #[test_case(Editor::Nano => format!("nano +{LINE_NUMBER},5 {FILE_NAME}"); "nano command")]
+    fn builtin_editor_command_for_file_not_in_utf8(editor: Editor) -> String {
+        let editor_command = EditorCommand::new(None, Some(editor)).unwrap();
+        let column = Column {
+            byte: None,
+            ..COLUMN
+        };
+        format!(
+            "{} {}",
+            editor_command.program(),
+            editor_command
+                .args(FILE_NAME, LINE_NUMBER, column)
+                .join(" ")
         )
     }
+
+    #[test_case("foo", 0 => Column { byte: Some(1), char: 1, display: 1 }; "line start")]
+    #[test_case("ab foo", 3 => Column { byte: Some(4), char: 4, display: 4 }; "ascii")]
+    #[test_case("zażółć foo", 11 => Column { byte: Some(12), char: 8, display: 8 }; "multibyte")]
+    #[test_case("日本 foo", 7 => Column { byte: Some(8), char: 4, display: 6 }; "wide")]
+    #[test_case("\tab\tfoo", 4 => Column { byte: Some(5), char: 5, display: 17 }; "tabs")]
+    #[test_case("first\nab foo", 9 => Column { byte: Some(4), char: 4, display: 4 }; "second line")]
+    fn column(text: &str, offset: usize) -> Column {
+        Column::new(text, offset)
+    }
 }
@@ -1,17 +1,36 @@
+pub mod archive;
+pub mod decompress;
+mod encoding;
+mod engine;
 pub mod file_entry;
+mod git;
 pub mod grep_match;
+mod preprocessor;
+mod progress;
+mod replace;
+pub mod revision;
 mod search_config;
 mod searcher;
 mod sink;
+mod stdin;
+mod temp_file;
 
+use std::fs::File;
 use std::io;
 use std::process::ExitStatus;
 use std::sync::mpsc;
 
-use crate::editor::EditorCommand;
+use crate::editor::{Column, EditorCommand};
 use crate::ui::result_list::ResultList;
+pub use encoding::FileEncoding;
+pub use engine::Engine;
+pub use git::GitScope;
+pub use preprocessor::Preprocessor;
+pub use progress::FileProgress;
+pub use replace::{patch_root, FileChange, Replacer, SearchedLine};
 pub use search_config::SearchConfig;
-use searcher::Event;
+use searcher::{CancelToken, Event};
+pub use stdin::{StdinBuffer, StdinLines, STDIN_NAME};
 
 use self::file_entry::FileEntry;
 
@@ -29,10 +48,19 @@ pub struct Ig {
     rx: mpsc::Receiver<Event>,
     state: State,
     editor_command: EditorCommand,
+    encoding: FileEncoding,
+    generation: u64,
+    cancel_token: CancelToken,
+    cancelled: bool,
+    progress: Option<FileProgress>,
+    diagnostics: Vec<String>,
+    notice: Option<String>,
+    /// Whether results are files of commits, named by their virtual paths.
+    history: bool,
 }
 
 impl Ig {
-    pub fn new(editor_command: EditorCommand) -> Self {
+    pub fn new(editor_command: EditorCommand, encoding: FileEncoding) -> Self {
         let (tx, rx) = mpsc::channel();
 
         Self {
@@ -40,18 +68,73 @@ impl Ig {
             rx,
             state: State::Idle,
             editor_command,
+            encoding,
+            generation: 0,
+            cancel_token: CancelToken::default(),
+            cancelled: false,
+            progress: None,
+            diagnostics: Vec::new(),
+            notice: None,
+            history: false,
         }
     }
 
-    fn try_spawn_editor(&self, file_name: &str, line_number: u64) -> io::Result<ExitStatus> {
-        let mut editor_process = self.editor_command.spawn(file_name, line_number)?;
+    fn try_spawn_editor(
+        &self,
+        file_name: &str,
+        line_number: u64,
+        mut column: Column,
+    ) -> io::Result<ExitStatus> {
+        // the column was counted in the line decoded to UTF-8, its bytes are not the file's
+        let is_utf8 = File::open(file_name).and_then(|file| self.encoding.is_utf8(file));
+        if !is_utf8.unwrap_or(true) {
+            column.byte = None;
+        }
+        let mut editor_process = self.editor_command.spawn(file_name, line_number, column)?;
         editor_process.wait()
     }
 
-    pub fn open_file_if_requested(&mut self, selected_entry: Option<(String, u64)>) {
+    pub fn open_file_if_requested(
+        &mut self,
+        selected_entry: Option<(String, u64)>,
+        column: Column,
+    ) {
         if let State::OpenFile(idle) = self.state {
             if let Some((ref file_name, line_number)) = selected_entry {
-                match self.try_spawn_editor(file_name, line_number) {
+                let extracted = if archive::split_member_path(file_name).is_some() {
+                    Some(archive::extract_member(file_name))
+                } else if self.history && revision::split_revision_path(file_name).is_some() {
+                    // a read-only copy, changes would not be kept in the history anyway
+                    Some(revision::extract_blob(file_name))
+                } else {
+                    None
+                };
+                let extracted_file;
+                let file_name = match extracted {
+                    Some(Ok(path)) => {
+                        extracted_file = path.to_string_lossy().into_owned();
+                        &extracted_file
+                    }
+                    Some(Err(error)) => {
+                        self.state = State::Error(format!("Cannot extract '{file_name}': {error}"));
+                        return;
+                    }
+                    None => file_name,
+                };
+                if decompress::is_compressed(file_name) {
+                    self.state = State::Error(format!(
+                        "Cannot open compressed file '{file_name}' in editor"
+                    ));
+                    return;
+                }
+                if file_name == STDIN_NAME {
+                    // reported without stopping a search of a stream that goes on
+                    self.diagnostics
+                        .push("Cannot open standard input in editor".into());
+                    self.state = if idle { State::Idle } else { State::Searching };
+                    return;
+                }
+                match self.try_spawn_editor(file_name, line_number, column) {
                     Ok(_) => self.state = if idle { State::Idle } else { State::Searching },
                     Err(_) => {
                         self.state = State::Error(format!(
@@ -69,9 +152,23 @@ impl Ig {
     pub fn handle_searcher_event(&mut self) -> Option<FileEntry> {
         while let Ok(event) = self.rx.try_recv() {
             match event {
-                Event::NewEntry(e) => return Some(e),
-                Event::SearchingFinished => self.state = State::Idle,
-                Event::Error => self.state = State::Exit,
+                Event::NewEntry(generation, e) if generation == self.generation => return Some(e),
+                Event::Progress(generation, progress) if generation == self.generation => {
+                    if !progress.is_done() {
+                        self.progress = Some(progress);
+                    } else if self.progress.as_ref().map(|p| &p.name) == Some(&progress.name) {
+                        self.progress = None;
+                    }
+                }
+                Event::SearchingFinished(generation) if generation == self.generation => {
+                    self.state = State::Idle;
+                    self.progress = None;
+                }
+                Event::Diagnostic(generation, message) if generation == self.generation => {
+                    self.diagnostics.push(message)
+                }
+                // stale event of a cancelled or replaced search
+                _ => (),
             }
         }
 
@@ -79,15 +176,68 @@ impl Ig {
     }
 
     pub fn search(&mut self, search_config: SearchConfig, result_list: &mut ResultList) {
-        if self.state == State::Idle {
-            *result_list = ResultList::default();
+        if self.is_idle() || self.is_searching() || self.last_error().is_some() {
+            self.stop_running_search();
+            self.cancelled = false;
+            self.diagnostics.clear();
+            self.notice = None;
+            result_list.clear();
+            if let Err(error) = search_config.validate() {
+                self.state = State::Error(format!("Invalid pattern: {error}"));
+                return;
+            }
             self.state = State::Searching;
-            searcher::search(search_config, self.tx.clone());
+            self.history = search_config.revisions.is_some();
+            self.cancel_token = CancelToken::default();
+            searcher::search(
+                search_config,
+                self.generation,
+                self.cancel_token.clone(),
+                self.tx.clone(),
+            );
         }
     }
 
+    /// Stops running search, keeping results found so far.
+    pub fn cancel_search(&mut self) {
+        if self.is_searching() {
+            self.stop_running_search();
+            self.cancelled = true;
+            self.state = State::Idle;
+        }
+    }
+
+    fn stop_running_search(&mut self) {
+        self.cancel_token.cancel();
+        self.generation += 1;
+        self.progress = None;
+    }
+
+    /// Errors of the last search that did not stop it, e.g. unreadable files.
+    pub fn diagnostics(&self) -> &[String] {
+        &self.diagnostics
+    }
+
+    pub fn add_diagnostic(&mut self, message: String) {
+        self.diagnostics.push(message);
+    }
+
+    /// Outcome of the last action on results, e.g. replacing matches, shown until next search.
+    pub fn notice(&self) -> Option<&str> {
+        self.notice.as_deref()
+    }
+
+    pub fn set_notice(&mut self, notice: String) {
+        self.notice = Some(notice);
+    }
+
+    /// Progress of the most recently reported file that takes a while to search.
+    pub fn file_progress(&self) -> Option<&FileProgress> {
+        self.progress.as_ref()
+    }
+
     pub fn open_file(&mut self) {
-        self.state = State::OpenFile(self.state == State::Idle);
+        self.state = State::OpenFile(!self.is_searching());
     }
 
     pub fn exit(&mut self) {
@@ -102,6 +252,10 @@ impl Ig {
         self.state == State::Searching
     }
 
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled
+    }
+
     pub fn last_error(&self) -> Option<&str> {
         if let State::Error(err) = &self.state {
             Some(err)
@@ -114,3 +268,114 @@ impl Ig {
         self.state == State::Exit
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ig::file_entry::EntryType;
+
+    fn ig() -> Ig {
+        Ig::new(
+            EditorCommand::new(Some("vim +{line_number} {file_name}".into()), None).unwrap(),
+            FileEncoding::Auto,
+        )
+    }
+
+    #[test]
+    fn cancel_keeps_state_idle_and_marks_search_cancelled() {
+        let mut ig = ig();
+        ig.state = State::Searching;
+
+        ig.cancel_search();
+
+        assert!(ig.is_idle());
+        assert!(ig.is_cancelled());
+    }
+
+    #[test]
+    fn events_of_stale_searches_are_dropped() {
+        let mut ig = ig();
+        ig.state = State::Searching;
+        ig.cancel_search();
+        ig.state = State::Searching;
+
+        ig.tx
+            .send(Event::NewEntry(
+                0,
+                FileEntry::new::<EntryType>("a".into(), vec![]),
+            ))
+            .unwrap();
+        ig.tx.send(Event::SearchingFinished(0)).unwrap();
+        assert!(ig.handle_searcher_event().is_none());
+        assert!(ig.is_searching());
+
+        ig.tx
+            .send(Event::NewEntry(
+                1,
+                FileEntry::new::<EntryType>("b".into(), vec![]),
+            ))
+            .unwrap();
+        ig.tx.send(Event::SearchingFinished(1)).unwrap();
+        assert!(ig.handle_searcher_event().is_some());
+        assert!(ig.handle_searcher_event().is_none());
+        assert!(ig.is_idle());
+    }
+
+    #[test]
+    fn stdin_is_searched_for_dash_path() {
+        let stdin = StdinBuffer::read_from(std::io::Cursor::new(b"foo\nbar\nfoo bar\n".to_vec()));
+        let config = SearchConfig::from(vec!["foo".into()], vec!["-".into()])
+            .unwrap()
+            .stdin(Some(stdin));
+        let (tx, rx) = mpsc::channel();
+        searcher::search(config, 0, CancelToken::default(), tx);
+
+        let mut matches = 0;
+        for event in rx {
+            match event {
+                Event::NewEntry(_, entry) => {
+                    assert_eq!(entry.name(), STDIN_NAME);
+                    matches += entry.get_matches_count();
+                }
+                Event::SearchingFinished(_) => break,
+                _ => (),
+            }
+        }
+        assert_eq!(matches, 2);
+    }
+
+    #[test]
+    fn diagnostics_do_not_stop_search() {
+        let mut ig = ig();
+        ig.state = State::Searching;
+
+        ig.tx
+            .send(Event::Diagnostic(0, "a: Permission denied".into()))
+            .unwrap();
+        ig.tx.send(Event::Diagnostic(1, "stale".into())).unwrap();
+        assert!(ig.handle_searcher_event().is_none());
+        assert!(ig.is_searching());
+        assert_eq!(ig.diagnostics(), ["a: Permission denied"]);
+    }
+
+    #[test]
+    fn progress_is_cleared_once_file_is_done() {
+        let mut ig = ig();
+        ig.state = State::Searching;
+        let progress = |name: &str, searched| FileProgress {
+            name: name.into(),
+            searched,
+            total: 100,
+        };
+
+        ig.tx.send(Event::Progress(0, progress("a", 10))).unwrap();
+        ig.tx.send(Event::Progress(0, progress("b", 10))).unwrap();
+        ig.tx.send(Event::Progress(0, progress("a", 100))).unwrap();
+        ig.handle_searcher_event();
+        assert_eq!(ig.file_progress(), Some(&progress("b", 10)));
+
+        ig.tx.send(Event::Progress(0, progress("b", 100))).unwrap();
+        ig.handle_searcher_event();
+        assert_eq!(ig.file_progress(), None);
+    }
+}
@@ -0,0 +1,220 @@
+use std::{
+    fs::{self, File},
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+
+use grep::cli::DecompressionReader;
+
+use super::{decompress, temp_file::write_temp_file};
+
+/// Separates the path of an archive from the path of its member, e.g. `bundle.tar.gz!/etc/app.conf`.
+pub const MEMBER_SEPARATOR: &str = "!/";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ArchiveFormat {
+    Tar,
+    Zip,
+}
+
+impl ArchiveFormat {
+    fn of(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_str()?.to_ascii_lowercase();
+        let has_extension =
+            |extensions: &[&str]| extensions.iter().any(|extension| name.ends_with(extension));
+
+        if has_extension(&[".tar", ".tgz", ".tbz2", ".txz"])
+            || (decompress::is_compressed(path)
+                && Path::new(&name)
+                    .file_stem()
+                    .is_some_and(|stem| stem.to_string_lossy().ends_with(".tar")))
+        {
+            Some(ArchiveFormat::Tar)
+        } else if has_extension(&[".zip", ".jar", ".war", ".ear"]) {
+            Some(ArchiveFormat::Zip)
+        } else {
+            None
+        }
+    }
+}
+
+/// Whether members of the file can be searched with `--archives`.
+pub fn is_archive(path: impl AsRef<Path>) -> bool {
+    ArchiveFormat::of(path.as_ref()).is_some()
+}
+
+/// Virtual path of an archive member.
+pub fn member_path(archive: &str, member: &str) -> String {
+    format!("{archive}{MEMBER_SEPARATOR}{member}")
+}
+
+/// Splits a virtual path of an archive member into the archive path and the member name.
+pub fn split_member_path(path: &str) -> Option<(&Path, &str)> {
+    path.match_indices(MEMBER_SEPARATOR)
+        .map(|(index, _)| {
+            (
+                Path::new(&path[..index]),
+                &path[index + MEMBER_SEPARATOR.len()..],
+            )
+        })
+        .find(|(archive, _)| is_archive(archive))
+}
+
+/// Calls `visit` with the name, size and content of every file in the archive, until it
+/// returns false. Compressed tar archives are decompressed like with `--search-zip`.
+pub(crate) fn for_each_member<F>(path: &Path, visit: F) -> io::Result<()>
+where
+    F: FnMut(&str, u64, &mut dyn Read) -> io::Result<bool>,
+{
+    match ArchiveFormat::of(path) {
+        Some(ArchiveFormat::Tar) if decompress::is_compressed(path) => {
+            let mut reader = DecompressionReader::new(path)?;
+            let visited = for_each_tar_member(&mut reader, visit);
+            // close explicitly, otherwise its errors would be printed over the UI
+            visited.and(reader.close())
+        }
+        Some(ArchiveFormat::Tar) => for_each_tar_member(File::open(path)?, visit),
+        Some(ArchiveFormat::Zip) => for_each_zip_member(File::open(path)?, visit),
+        None => Err(io::Error::other("not an archive")),
+    }
+}
+
+fn for_each_tar_member<F>(reader: impl Read, mut visit: F) -> io::Result<()>
+where
+    F: FnMut(&str, u64, &mut dyn Read) -> io::Result<bool>,
+{
+    let mut archive = tar::Archive::new(reader);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let name = entry.path()?.to_string_lossy().into_owned();
+        let size = entry.size();
+        if !visit(&name, size, &mut entry)? {
+            break;
+        }
+    }
+    Ok(())
+}
+
+fn for_each_zip_member<F>(file: File, mut visit: F) -> io::Result<()>
+where
+    F: FnMut(&str, u64, &mut dyn Read) -> io::Result<bool>,
+{
+    let mut archive = zip::ZipArchive::new(file)?;
+    for index in 0..archive.len() {
+        let mut member = archive.by_index(index)?;
+        if member.is_dir() {
+            continue;
+        }
+        let name = member.name().to_owned();
+        let size = member.size();
+        if !visit(&name, size, &mut member)? {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Reads the content of an archive member given by its virtual path.
+pub fn read_member(path: &str) -> io::Result<Vec<u8>> {
+    let (archive, member) = split_member_path(path)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "not an archive member"))?;
+    let mut content = None;
+    for_each_member(archive, |name, size, reader| {
+        if name != member {
+            return Ok(true);
+        }
+        // the size comes from the archive, it only limits the read and is not allocated up front
+        let mut bytes = Vec::new();
+        reader.take(size).read_to_end(&mut bytes)?;
+        content = Some(bytes);
+        Ok(false)
+    })?;
+    content.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "member not found"))
+}
+
+/// Extracts an archive member given by its virtual path to a temporary file, so that it
+/// can be opened in an editor. Returns path of the extracted file.
+pub fn extract_member(path: &str) -> io::Result<PathBuf> {
+    let (archive, member) = split_member_path(path)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "not an archive member"))?;
+    // members of archives with the same name in different directories are kept apart
+    let target = fs::canonicalize(archive)?.join(member);
+    write_temp_file(&target, &read_member(path)?, false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TempDir;
+    use test_case::test_case;
+
+    #[test_case("bundle.tar" => true)]
+    #[test_case("bundle.tar.gz" => true)]
+    #[test_case("bundle.TGZ" => true)]
+    #[test_case("bundle.tar.zst" => true)]
+    #[test_case("lib/app.jar" => true)]
+    #[test_case("app.zip" => true)]
+    #[test_case("app.log.gz" => false)]
+    #[test_case("src/main.rs" => false)]
+    fn archive(path: &str) -> bool {
+        is_archive(path)
+    }
+
+    #[test_case("bundle.tar.gz!/etc/app.conf" => Some(("bundle.tar.gz", "etc/app.conf")))]
+    #[test_case("a!/b.zip!/c.txt" => Some(("a!/b.zip", "c.txt")))]
+    #[test_case("a!/b.txt" => None)]
+    fn split(path: &str) -> Option<(&str, &str)> {
+        split_member_path(path).map(|(archive, member)| (archive.to_str().unwrap(), member))
+    }
+
+    #[test]
+    fn tar_and_zip_members() {
+        let dir = TempDir::new("archive");
+
+        let tar_path = dir.join("bundle.tar");
+        let mut tar = tar::Builder::new(File::create(&tar_path).unwrap());
+        let mut header = tar::Header::new_gnu();
+        header.set_size(9);
+        header.set_mode(0o644);
+        tar.append_data(&mut header, "etc/app.conf", &b"port=8080"[..])
+            .unwrap();
+        tar.finish().unwrap();
+
+        let zip_path = dir.join("app.jar");
+        let mut zip = zip::ZipWriter::new(File::create(&zip_path).unwrap());
+        zip.add_directory("META-INF", Default::default()).unwrap();
+        zip.start_file("META-INF/MANIFEST.MF", Default::default())
+            .unwrap();
+        io::Write::write_all(&mut zip, b"Main-Class: App").unwrap();
+        zip.finish().unwrap();
+
+        for (archive, member, content) in [
+            (&tar_path, "etc/app.conf", "port=8080"),
+            (&zip_path, "META-INF/MANIFEST.MF", "Main-Class: App"),
+        ] {
+            let mut members = Vec::new();
+            for_each_member(archive, |name, _, _| {
+                members.push(name.to_owned());
+                Ok(true)
+            })
+            .unwrap();
+            assert_eq!(members, [member]);
+
+            let path = member_path(&archive.to_string_lossy(), member);
+            assert_eq!(read_member(&path).unwrap(), content.as_bytes());
+            let extracted = extract_member(&path).unwrap();
+            assert!(extracted.ends_with(
+                fs::canonicalize(archive)
+                    .unwrap()
+                    .strip_prefix("/")
+                    .unwrap()
+                    .join(member)
+            ));
+            assert_eq!(fs::read(&extracted).unwrap(), content.as_bytes());
+            fs::remove_file(extracted).unwrap();
+        }
+    }
+}
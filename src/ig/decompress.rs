@@ -0,0 +1,37 @@
+use std::{io::Read, path::Path};
+
+use grep::cli::{DecompressionMatcher, DecompressionReader};
+
+/// Whether the file is compressed in one of the formats supported by `--search-zip`.
+pub fn is_compressed(path: impl AsRef<Path>) -> bool {
+    DecompressionMatcher::new().has_command(path)
+}
+
+/// Reads the whole file, decompressing it first if it is compressed.
+pub fn read_decompressed(path: impl AsRef<Path>) -> std::io::Result<Vec<u8>> {
+    if !is_compressed(&path) {
+        return std::fs::read(path);
+    }
+
+    let mut reader = DecompressionReader::new(path)?;
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    reader.close()?;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case("logs/app.log.gz" => true)]
+    #[test_case("logs/app.log.xz" => true)]
+    #[test_case("logs/app.log.zst" => true)]
+    #[test_case("logs/app.log.bz2" => true)]
+    #[test_case("logs/app.log" => false)]
+    #[test_case("src/main.rs" => false)]
+    fn compressed(path: &str) -> bool {
+        is_compressed(path)
+    }
+}
@@ -0,0 +1,234 @@
+use std::{
+    borrow::Cow,
+    fmt,
+    io::{self, Read},
+    str::FromStr,
+};
+
+use anyhow::anyhow;
+use chardetng::EncodingDetector;
+use encoding_rs::Encoding;
+
+/// Number of bytes inspected when guessing encoding of a file.
+const DETECTION_PREFIX_LEN: usize = 8 * 1024;
+
+/// Reader of content whose beginning was already read to guess its encoding.
+pub(crate) type PeekedReader<R> = io::Chain<io::Cursor<Vec<u8>>, R>;
+
+/// Text encoding of searched files.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FileEncoding {
+    /// UTF-8, unless a BOM says otherwise or the content is not valid UTF-8,
+    /// in which case the encoding is guessed.
+    #[default]
+    Auto,
+    Fixed(&'static Encoding),
+}
+
+impl FileEncoding {
+    /// Encoding the searcher should transcode from, `None` means content is searched as is.
+    pub(crate) fn searcher_encoding(&self) -> Option<grep::searcher::Encoding> {
+        match self {
+            FileEncoding::Auto => None,
+            FileEncoding::Fixed(encoding) => grep::searcher::Encoding::new(encoding.name()).ok(),
+        }
+    }
+
+    /// Guesses encoding of content from its beginning in `Auto` mode, read from `reader`
+    /// which is returned with the read bytes put back in front. The guess is `None` if content
+    /// starts with a BOM, which is handled by the searcher, or with valid UTF-8.
+    /// Unless `fill_prefix`, only the first read is inspected, so that a stream is not waited for.
+    pub(crate) fn detect_reader<R: Read>(
+        &self,
+        mut reader: R,
+        fill_prefix: bool,
+    ) -> io::Result<(Option<&'static Encoding>, PeekedReader<R>)> {
+        let mut prefix = Vec::new();
+        if *self == FileEncoding::Auto {
+            prefix.resize(DETECTION_PREFIX_LEN, 0);
+            let mut len = 0;
+            while len < prefix.len() {
+                match reader.read(&mut prefix[len..]) {
+                    Ok(0) => break,
+                    Ok(read) => {
+                        len += read;
+                        if !fill_prefix {
+                            break;
+                        }
+                    }
+                    Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(error) => return Err(error),
+                }
+            }
+            prefix.truncate(len);
+        }
+
+        let encoding = match self {
+            FileEncoding::Auto => Self::detect_prefix(&prefix),
+            FileEncoding::Fixed(_) => None,
+        };
+        Ok((encoding, io::Cursor::new(prefix).chain(reader)))
+    }
+
+    /// Whether content read from `reader` is searched as UTF-8, rather than transcoded from
+    /// another encoding, judged from its beginning the same way as when it is searched.
+    pub(crate) fn is_utf8<R: Read>(&self, reader: R) -> io::Result<bool> {
+        let mut prefix = Vec::with_capacity(DETECTION_PREFIX_LEN);
+        reader
+            .take(DETECTION_PREFIX_LEN as u64)
+            .read_to_end(&mut prefix)?;
+
+        // the searcher lets a BOM override the configured encoding
+        Ok(match Encoding::for_bom(&prefix) {
+            Some((encoding, _)) => encoding == encoding_rs::UTF_8,
+            None => match self {
+                FileEncoding::Auto => Self::detect_prefix(&prefix).is_none(),
+                FileEncoding::Fixed(encoding) => *encoding == encoding_rs::UTF_8,
+            },
+        })
+    }
+
+    fn detect_prefix(prefix: &[u8]) -> Option<&'static Encoding> {
+        if Encoding::for_bom(prefix).is_some() {
+            return None;
+        }
+        match std::str::from_utf8(prefix) {
+            Ok(_) => None,
+            // prefix may end in the middle of a multi-byte character
+            Err(error) if error.error_len().is_none() => None,
+            Err(_) => Some(Self::detect(prefix)),
+        }
+    }
+
+    fn detect(bytes: &[u8]) -> &'static Encoding {
+        if let Some((encoding, _)) = Encoding::for_bom(bytes) {
+            return encoding;
+        }
+
+        let prefix = &bytes[..bytes.len().min(DETECTION_PREFIX_LEN)];
+        let mut detector = EncodingDetector::new();
+        detector.feed(prefix, prefix.len() == bytes.len());
+        detector.guess(None, false)
+    }
+
+    /// Decodes whole file content, a BOM always takes precedence over the configured encoding.
+    pub fn decode<'a>(&self, bytes: &'a [u8]) -> Cow<'a, str> {
+        let encoding = match self {
+            FileEncoding::Fixed(encoding) => encoding,
+            FileEncoding::Auto => match Encoding::for_bom(bytes) {
+                Some((encoding, _)) => encoding,
+                None => match std::str::from_utf8(bytes) {
+                    Ok(text) => return Cow::Borrowed(text),
+                    Err(_) => Self::detect(bytes),
+                },
+            },
+        };
+        encoding.decode(bytes).0
+    }
+}
+
+impl FromStr for FileEncoding {
+    type Err = anyhow::Error;
+
+    fn from_str(label: &str) -> Result<Self, Self::Err> {
+        if label.eq_ignore_ascii_case("auto") {
+            return Ok(FileEncoding::Auto);
+        }
+
+        Encoding::for_label_no_replacement(label.as_bytes())
+            .map(FileEncoding::Fixed)
+            .ok_or_else(|| anyhow!("unknown encoding '{label}'"))
+    }
+}
+
+impl fmt::Display for FileEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FileEncoding::Auto => write!(f, "auto"),
+            FileEncoding::Fixed(encoding) => write!(f, "{}", encoding.name().to_lowercase()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case("auto" => FileEncoding::Auto)]
+    #[test_case("gbk" => FileEncoding::Fixed(encoding_rs::GBK))]
+    #[test_case("Shift_JIS" => FileEncoding::Fixed(encoding_rs::SHIFT_JIS))]
+    #[test_case("utf-16le" => FileEncoding::Fixed(encoding_rs::UTF_16LE))]
+    fn parse(label: &str) -> FileEncoding {
+        label.parse().unwrap()
+    }
+
+    #[test]
+    fn parse_unknown() {
+        assert!("klingon".parse::<FileEncoding>().is_err());
+    }
+
+    #[test]
+    fn decode_auto() {
+        let (gbk, _, _) = encoding_rs::GBK.encode("传输速度很快，传输速度很稳定。");
+        assert_eq!(
+            FileEncoding::Auto.decode(&gbk),
+            "传输速度很快，传输速度很稳定。"
+        );
+
+        let utf16 = [0xFF, 0xFE, b'i', 0, b'g', 0];
+        assert_eq!(FileEncoding::Auto.decode(&utf16), "ig");
+
+        assert_eq!(FileEncoding::Auto.decode("ąę".as_bytes()), "ąę");
+    }
+
+    #[test]
+    fn detect_reader() {
+        let detect = |encoding: FileEncoding, bytes: &[u8]| {
+            let (detected, mut reader) = encoding.detect_reader(bytes, true).unwrap();
+            let mut content = Vec::new();
+            reader.read_to_end(&mut content).unwrap();
+            assert_eq!(content, bytes, "content is read whole");
+            detected
+        };
+
+        let (gbk, _, _) = encoding_rs::GBK.encode("传输速度很快，传输速度很稳定。");
+        assert_eq!(detect(FileEncoding::Auto, &gbk), Some(encoding_rs::GBK));
+        assert_eq!(detect(FileEncoding::Fixed(encoding_rs::GBK), &gbk), None);
+        assert_eq!(
+            detect(
+                FileEncoding::Auto,
+                "传输速度很快，传输速度很稳定。".as_bytes()
+            ),
+            None
+        );
+        assert_eq!(detect(FileEncoding::Auto, &[0xFF, 0xFE, b'i', 0]), None);
+    }
+
+    #[test]
+    fn is_utf8() {
+        let (gbk, _, _) = encoding_rs::GBK.encode("传输速度很快，传输速度很稳定。");
+        let utf8 = "传输速度很快，传输速度很稳定。".as_bytes();
+        let utf16 = [0xFF, 0xFE, b'i', 0, b'g', 0];
+
+        assert!(!FileEncoding::Auto.is_utf8(&gbk[..]).unwrap());
+        assert!(FileEncoding::Auto.is_utf8(utf8).unwrap());
+        assert!(!FileEncoding::Auto.is_utf8(&utf16[..]).unwrap());
+        assert!(!FileEncoding::Fixed(encoding_rs::GBK).is_utf8(utf8).unwrap());
+        assert!(FileEncoding::Fixed(encoding_rs::UTF_8)
+            .is_utf8(utf8)
+            .unwrap());
+        assert!(!FileEncoding::Fixed(encoding_rs::UTF_8)
+            .is_utf8(&utf16[..])
+            .unwrap());
+    }
+
+    #[test]
+    fn decode_fixed() {
+        let (sjis, _, _) = encoding_rs::SHIFT_JIS.encode("日本語");
+        assert_eq!(
+            FileEncoding::Fixed(encoding_rs::SHIFT_JIS).decode(&sjis),
+            "日本語"
+        );
+    }
+}
@@ -0,0 +1,191 @@
+use anyhow::Result;
+use clap::ArgEnum;
+use grep::regex::{RegexMatcher, RegexMatcherBuilder};
+use strum_macros::Display;
+
+use super::SearchConfig;
+
+#[derive(Display, Default, PartialEq, Eq, Copy, Clone, Debug, ArgEnum)]
+#[strum(serialize_all = "lowercase")]
+pub enum Engine {
+    /// Rust regex engine, fast but without lookaround and backreferences.
+    #[default]
+    Default,
+    /// PCRE2 engine, available when built with the `pcre2` feature.
+    Pcre2,
+    /// Use the default engine, fall back to PCRE2 if the pattern requires it.
+    Auto,
+}
+
+/// Matcher for all patterns together with one matcher per pattern, the latter are used
+/// to tell which pattern produced a match and are empty if there is only one pattern.
+pub(crate) enum GrepMatcher {
+    Regex(RegexMatcher, Vec<RegexMatcher>),
+    #[cfg(feature = "pcre2")]
+    Pcre2(grep::pcre2::RegexMatcher, Vec<grep::pcre2::RegexMatcher>),
+}
+
+impl GrepMatcher {
+    pub(crate) fn new(config: &SearchConfig) -> Result<Self> {
+        match config.engine {
+            Engine::Default => Self::regex(config),
+            Engine::Pcre2 => Self::pcre2(config),
+            Engine::Auto => Self::regex(config).or_else(|regex_error| {
+                if cfg!(feature = "pcre2") {
+                    Self::pcre2(config).map_err(|_| regex_error)
+                } else {
+                    Err(regex_error)
+                }
+            }),
+        }
+    }
+
+    fn regex(config: &SearchConfig) -> Result<Self> {
+        let mut builder = RegexMatcherBuilder::new();
+        builder
+            .line_terminator((!config.multiline).then_some(b'\n'))
+            .multi_line(config.multiline)
+            .case_insensitive(config.case_insensitive)
+            .case_smart(config.case_smart)
+            .word(config.word_regexp)
+            .fixed_strings(config.fixed_strings)
+            .whole_line(config.line_regexp);
+
+        let matcher = builder
+            .build_many(&config.patterns)
+            .map_err(|error| Self::syntax_error(config).unwrap_or_else(|| error.into()))?;
+        let pattern_matchers = if config.patterns.len() > 1 {
+            config
+                .patterns
+                .iter()
+                .map(|pattern| builder.build(pattern))
+                .collect::<Result<_, _>>()?
+        } else {
+            Vec::new()
+        };
+
+        Ok(GrepMatcher::Regex(matcher, pattern_matchers))
+    }
+
+    /// The matcher builder joins patterns into one regex, so its errors point into the
+    /// joined regex. Parsing patterns one by one points at the invalid part of a pattern.
+    fn syntax_error(config: &SearchConfig) -> Option<anyhow::Error> {
+        if config.fixed_strings {
+            return None;
+        }
+        config
+            .patterns
+            .iter()
+            .find_map(|pattern| match regex::Regex::new(pattern) {
+                Err(error @ regex::Error::Syntax(_)) => Some(error.into()),
+                _ => None,
+            })
+    }
+
+    #[cfg(feature = "pcre2")]
+    fn pcre2(config: &SearchConfig) -> Result<Self> {
+        let mut builder = grep::pcre2::RegexMatcherBuilder::new();
+        builder
+            .utf(true)
+            .jit_if_available(true)
+            .multi_line(config.multiline)
+            .caseless(config.case_insensitive)
+            .case_smart(config.case_smart)
+            .word(config.word_regexp)
+            .fixed_strings(config.fixed_strings)
+            .whole_line(config.line_regexp);
+
+        let matcher = builder.build_many(&config.patterns)?;
+        let pattern_matchers = if config.patterns.len() > 1 {
+            config
+                .patterns
+                .iter()
+                .map(|pattern| builder.build(pattern))
+                .collect::<Result<_, _>>()?
+        } else {
+            Vec::new()
+        };
+
+        Ok(GrepMatcher::Pcre2(matcher, pattern_matchers))
+    }
+
+    #[cfg(not(feature = "pcre2"))]
+    fn pcre2(_: &SearchConfig) -> Result<Self> {
+        Err(anyhow::anyhow!(
+            "PCRE2 engine is not available, rebuild higrep with the `pcre2` feature"
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use grep::matcher::Matcher;
+    use test_case::test_case;
+
+    fn config(pattern: &str, engine: Engine) -> SearchConfig {
+        SearchConfig::from(vec![pattern.into()], vec![])
+            .unwrap()
+            .engine(engine)
+    }
+
+    #[test_case("foo\\w+", Engine::Default => true; "valid default")]
+    #[test_case("foo(", Engine::Default => false; "invalid default")]
+    #[test_case("foo(?!bar)", Engine::Default => false; "lookahead default")]
+    #[test_case("(a)\\1", Engine::Default => false; "backreference default")]
+    fn build_matcher(pattern: &str, engine: Engine) -> bool {
+        GrepMatcher::new(&config(pattern, engine)).is_ok()
+    }
+
+    #[test]
+    fn validate_reports_error_position() {
+        let error = config("foo(", Engine::Default)
+            .validate()
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("    foo(\n       ^\n"));
+        assert!(error.contains("unclosed group"));
+    }
+
+    #[test_case("a.b[0]", "x = a.b[0]; axb[0]" => vec![(4, 10)]; "fixed strings")]
+    #[test_case("foo(", "foo(bar)" => vec![(0, 4)]; "fixed strings invalid regex")]
+    fn fixed_strings_offsets(pattern: &str, line: &str) -> Vec<(usize, usize)> {
+        let config = config(pattern, Engine::Default).fixed_strings(true);
+        find_offsets(&config, line)
+    }
+
+    #[test_case("a = 1;", "let a = 1;" => Vec::<(usize, usize)>::new(); "partial line")]
+    #[test_case("let a = \\d;", "let a = 1;" => vec![(0, 10)]; "whole line")]
+    fn line_regexp_offsets(pattern: &str, line: &str) -> Vec<(usize, usize)> {
+        let config = config(pattern, Engine::Default).line_regexp(true);
+        find_offsets(&config, line)
+    }
+
+    fn find_offsets(config: &SearchConfig, line: &str) -> Vec<(usize, usize)> {
+        let mut offsets = vec![];
+        let collect = |m: grep::matcher::Match| {
+            offsets.push((m.start(), m.end()));
+            true
+        };
+        match GrepMatcher::new(config).unwrap() {
+            GrepMatcher::Regex(matcher, _) => matcher.find_iter(line.as_bytes(), collect).unwrap(),
+            #[cfg(feature = "pcre2")]
+            GrepMatcher::Pcre2(matcher, _) => matcher.find_iter(line.as_bytes(), collect).unwrap(),
+        }
+        offsets
+    }
+
+    #[cfg(feature = "pcre2")]
+    #[test_case("foo(?!bar)", Engine::Pcre2 => true; "lookahead pcre2")]
+    #[test_case("(a)\\1", Engine::Auto => true; "backreference auto")]
+    #[test_case("foo(", Engine::Auto => false; "invalid auto")]
+    fn build_pcre2_matcher(pattern: &str, engine: Engine) -> bool {
+        GrepMatcher::new(&config(pattern, engine)).is_ok()
+    }
+
+    #[cfg(not(feature = "pcre2"))]
+    #[test]
+    fn pcre2_not_available() {
+        assert!(GrepMatcher::new(&config("foo", Engine::Pcre2)).is_err());
+    }
+}
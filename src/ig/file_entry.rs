@@ -1,33 +1,112 @@
+use std::{
+    fs::Metadata,
+    sync::atomic::{AtomicU64, Ordering},
+    time::SystemTime,
+};
+
+use crate::editor::Column;
+
 use super::grep_match::GrepMatch;
 
 pub enum EntryType {
-    Header(String),
-    Match(u64, String, Vec<(usize, usize)>),
+    Header(FileHeader),
+    /// Line number, text, match offsets and column of the first match if the line was truncated.
+    Match(u64, String, Vec<(usize, usize, usize)>, Option<Column>),
+    Context(u64, String),
+    ContextBreak,
+    /// Match in a binary file, holds line number and offset of the first binary byte.
+    BinaryMatch(u64, u64),
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct FileHeader {
+    pub name: String,
+    /// Number of matches not shown because of `--max-count`.
+    pub more_matches: u64,
+    pub modified: Option<SystemTime>,
+    pub accessed: Option<SystemTime>,
+    pub created: Option<SystemTime>,
+}
+
+impl EntryType {
+    pub fn is_match(&self) -> bool {
+        matches!(self, EntryType::Match(..) | EntryType::BinaryMatch(..))
+    }
+}
+
+impl From<GrepMatch> for EntryType {
+    fn from(m: GrepMatch) -> Self {
+        EntryType::Match(m.line_number, m.text, m.match_offsets, m.truncated_column)
+    }
 }
 
-pub struct FileEntry(Vec<EntryType>);
+pub struct FileEntry {
+    id: u64,
+    entries: Vec<EntryType>,
+    continued: bool,
+}
 
 impl FileEntry {
-    pub fn new(name: String, matches: Vec<GrepMatch>) -> Self {
-        Self(
-            std::iter::once(EntryType::Header(name))
-                .chain(
-                    matches
-                        .into_iter()
-                        .map(|m| EntryType::Match(m.line_number, m.text, m.match_offsets)),
-                )
-                .collect(),
-        )
+    /// Creates an entry of a file with a new id, files with the same name are kept apart.
+    pub fn new<E: Into<EntryType>>(name: String, entries: Vec<E>) -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            entries: std::iter::once(EntryType::Header(FileHeader {
+                name,
+                ..Default::default()
+            }))
+            .chain(entries.into_iter().map(Into::into))
+            .collect(),
+            continued: false,
+        }
+    }
+
+    /// Marks the entry as a next batch of matches of the file whose first entry has `id`.
+    /// Its header replaces the one sent before.
+    pub fn continued(mut self, id: u64) -> Self {
+        self.id = id;
+        self.continued = true;
+        self
+    }
+
+    pub fn is_continued(&self) -> bool {
+        self.continued
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn more_matches(mut self, count: u64) -> Self {
+        if let Some(EntryType::Header(header)) = self.entries.first_mut() {
+            header.more_matches = count;
+        }
+        self
+    }
+
+    /// Stores file times used for sorting.
+    pub fn metadata(mut self, metadata: &Metadata) -> Self {
+        if let Some(EntryType::Header(header)) = self.entries.first_mut() {
+            header.modified = metadata.modified().ok();
+            header.accessed = metadata.accessed().ok();
+            header.created = metadata.created().ok();
+        }
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        match self.entries.first() {
+            Some(EntryType::Header(header)) => &header.name,
+            _ => "",
+        }
     }
 
     pub fn get_matches_count(&self) -> usize {
-        self.0
-            .iter()
-            .filter(|&e| matches!(e, EntryType::Match(_, _, _)))
-            .count()
+        self.entries.iter().filter(|&e| e.is_match()).count()
     }
 
     pub fn get_entries(self) -> Vec<EntryType> {
-        self.0
+        self.entries
     }
 }
@@ -0,0 +1,261 @@
+use std::{
+    collections::HashMap,
+    fmt::{self, Display, Formatter},
+    fs, io, iter,
+    ops::Range,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// Part of a git repository which is searched.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum GitScope {
+    /// All files, regardless of git.
+    #[default]
+    All,
+    /// Files tracked by git.
+    Tracked,
+    /// Files changed in the working tree or the index against HEAD.
+    Changed,
+    /// Lines changed in the working tree against the given revision.
+    Diff(String),
+}
+
+impl GitScope {
+    /// Scope following this one when toggled at runtime, `rev` is used for `Diff`.
+    pub fn next(&self, rev: &str) -> Self {
+        match self {
+            GitScope::All => GitScope::Tracked,
+            GitScope::Tracked => GitScope::Changed,
+            GitScope::Changed => GitScope::Diff(rev.into()),
+            GitScope::Diff(_) => GitScope::All,
+        }
+    }
+}
+
+impl Display for GitScope {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            GitScope::All => write!(f, "all files"),
+            GitScope::Tracked => write!(f, "git tracked"),
+            GitScope::Changed => write!(f, "git changed"),
+            GitScope::Diff(rev) => write!(f, "git diff {rev}"),
+        }
+    }
+}
+
+/// Files of a git scope, listed with the `git` command in the local repository
+/// of a searched path.
+pub(crate) struct GitFilter {
+    /// Canonical paths of files in scope, with ranges of line numbers in scope.
+    files: HashMap<PathBuf, Vec<Range<u64>>>,
+}
+
+impl GitFilter {
+    /// Returns `None` if files are not filtered.
+    pub(crate) fn new(scope: &GitScope, path: &Path) -> io::Result<Option<Self>> {
+        let names = |output: String| {
+            output
+                .split_terminator('\0')
+                .map(|name| (name.to_owned(), iter::once(1..u64::MAX).collect()))
+                .collect::<Vec<_>>()
+        };
+
+        let dir = command_dir(path);
+        let files = match scope {
+            GitScope::All => return Ok(None),
+            GitScope::Tracked => names(git(dir, &["ls-files", "-z", "--full-name", ":/"])?),
+            GitScope::Changed => names(git(dir, &["diff", "--name-only", "-z", "HEAD", "--"])?),
+            // prefixes are given, configuration like `diff.noprefix` would change them
+            GitScope::Diff(rev) => parse_hunks(&git(
+                dir,
+                &[
+                    "diff",
+                    "-U0",
+                    "--no-color",
+                    "--no-ext-diff",
+                    "--src-prefix=a/",
+                    "--dst-prefix=b/",
+                    rev,
+                    "--",
+                ],
+            )?),
+        };
+
+        let root = PathBuf::from(git(dir, &["rev-parse", "--show-toplevel"])?.trim_end());
+        Ok(Some(Self {
+            files: files
+                .into_iter()
+                .map(|(name, lines)| (root.join(name), lines))
+                .collect(),
+        }))
+    }
+
+    /// Ranges of line numbers of the file in scope, `None` if the whole file is out of it.
+    pub(crate) fn lines(&self, path: &Path) -> Option<&[Range<u64>]> {
+        let path = fs::canonicalize(path).ok()?;
+        self.files.get(&path).map(Vec::as_slice)
+    }
+}
+
+/// Output of a `git` command run in `dir`.
+pub(super) fn git(dir: &Path, args: &[&str]) -> io::Result<String> {
+    git_bytes(dir, args).map(|output| String::from_utf8_lossy(&output).into_owned())
+}
+
+pub(super) fn git_bytes(dir: &Path, args: &[&str]) -> io::Result<Vec<u8>> {
+    let output = git_command(dir).args(args).output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "git {}: {}",
+            args[0],
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(output.stdout)
+}
+
+pub(super) fn git_command(dir: &Path) -> Command {
+    let mut command = Command::new("git");
+    command
+        .arg("-C")
+        .arg(dir)
+        .args(["-c", "core.quotePath=false"]);
+    command
+}
+
+/// Directory in which git commands about `path` are run.
+pub(super) fn command_dir(path: &Path) -> &Path {
+    if path.is_dir() {
+        path
+    } else {
+        path.parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .unwrap_or(Path::new("."))
+    }
+}
+
+/// Ranges of line numbers added or changed by each file of a diff without context lines.
+fn parse_hunks(diff: &str) -> Vec<(String, Vec<Range<u64>>)> {
+    let mut files: Vec<(String, Vec<Range<u64>>)> = Vec::new();
+    // content lines may look like headers, these are only expected before the first hunk
+    let mut in_header = false;
+    for line in diff.lines() {
+        if line.starts_with("diff ") {
+            in_header = true;
+        } else if let Some(name) = line.strip_prefix("+++ ").filter(|_| in_header) {
+            // deleted files have no new name
+            if let Some(name) = name.strip_prefix("b/") {
+                files.push((name.into(), Vec::new()));
+            }
+        } else if let Some(hunk) = line.strip_prefix("@@ ") {
+            in_header = false;
+            let Some((_, lines)) = files.last_mut() else {
+                continue;
+            };
+            // e.g. `@@ -10,2 +12,3 @@`, where the count of 1 may be omitted
+            let new_lines = hunk
+                .split(' ')
+                .find_map(|range| range.strip_prefix('+'))
+                .unwrap_or_default();
+            let (start, count) = new_lines.split_once(',').unwrap_or((new_lines, "1"));
+            if let (Ok(start), Ok(count)) = (start.parse::<u64>(), count.parse::<u64>()) {
+                // hunks only removing lines have nothing to match
+                if count > 0 {
+                    lines.push(start..start + count);
+                }
+            }
+        }
+    }
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::GitRepo;
+
+    #[test]
+    fn hunks_of_diff() {
+        let diff = "\
+diff --git a/src/a.rs b/src/a.rs
+index 1111111..2222222 100644
+--- a/src/a.rs
++++ b/src/a.rs
+@@ -3 +3 @@ fn main() {
+-    foo();
++    bar();
+@@ -10,0 +11,2 @@ fn main() {
++++ looks like a header
++    baz();
+@@ -20,2 +21,0 @@ fn main() {
+-    old();
+-    older();
+diff --git a/gone.rs b/gone.rs
+deleted file mode 100644
+--- a/gone.rs
++++ /dev/null
+@@ -1 +0,0 @@
+-fn gone() {}
+";
+        assert_eq!(
+            parse_hunks(diff),
+            [("src/a.rs".to_owned(), vec![3..4, 11..13])]
+        );
+    }
+
+    #[test]
+    fn scope_cycles_through_all_variants() {
+        let mut scope = GitScope::All;
+        let mut scopes = Vec::new();
+        for _ in 0..4 {
+            scope = scope.next("main");
+            scopes.push(scope.to_string());
+        }
+        assert_eq!(
+            scopes,
+            ["git tracked", "git changed", "git diff main", "all files"]
+        );
+    }
+
+    #[test]
+    fn files_of_scopes() {
+        let dir = GitRepo::new("git");
+        fs::write(dir.join("committed"), "1\n2\n3\n").unwrap();
+        fs::write(dir.join("unchanged"), "1\n").unwrap();
+        dir.commit("init");
+        fs::write(dir.join("committed"), "1\ntwo\n3\n").unwrap();
+        fs::write(dir.join("untracked"), "1\n").unwrap();
+
+        let lines = |scope: GitScope, name: &str| {
+            GitFilter::new(&scope, &dir)
+                .unwrap()
+                .unwrap()
+                .lines(&dir.join(name))
+                .map(|lines| {
+                    lines
+                        .iter()
+                        .map(|lines| (lines.start, lines.end))
+                        .collect::<Vec<_>>()
+                })
+        };
+        assert!(lines(GitScope::Tracked, "unchanged").is_some());
+        assert!(lines(GitScope::Tracked, "untracked").is_none());
+        assert!(lines(GitScope::Changed, "committed").is_some());
+        assert!(lines(GitScope::Changed, "unchanged").is_none());
+        assert_eq!(
+            lines(GitScope::Diff("HEAD".into()), "committed"),
+            Some(vec![(2, 3)])
+        );
+        assert!(GitFilter::new(&GitScope::All, &dir).unwrap().is_none());
+
+        for config in ["diff.noprefix", "diff.mnemonicPrefix"] {
+            dir.git(&["config", config, "true"]);
+            assert_eq!(
+                lines(GitScope::Diff("HEAD".into()), "committed"),
+                Some(vec![(2, 3)]),
+                "{config}"
+            );
+        }
+    }
+}
@@ -1,15 +1,27 @@
+use crate::editor::Column;
+
 pub struct GrepMatch {
     pub line_number: u64,
     pub text: String,
-    pub match_offsets: Vec<(usize, usize)>,
+    /// Byte offsets of matches in `text`, each with the index of the pattern that produced it.
+    pub match_offsets: Vec<(usize, usize, usize)>,
+    /// Column of the first match in the whole line, if `text` is only a part of it.
+    pub truncated_column: Option<Column>,
 }
 
 impl GrepMatch {
-    pub fn new(line_number: u64, text: String, match_offsets: Vec<(usize, usize)>) -> Self {
+    pub fn new(line_number: u64, text: String, match_offsets: Vec<(usize, usize, usize)>) -> Self {
         Self {
             line_number,
             text,
             match_offsets,
+            truncated_column: None,
         }
     }
+
+    /// Marks the text as a shortened version of a too long line.
+    pub fn truncated(mut self, column: Column) -> Self {
+        self.truncated_column = Some(column);
+        self
+    }
 }
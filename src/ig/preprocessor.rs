@@ -0,0 +1,80 @@
+use std::{
+    fs::File,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use anyhow::Result;
+use grep::cli::{CommandReader, CommandReaderBuilder};
+use ignore::overrides::{Override, OverrideBuilder};
+
+/// Command through which files are piped before searching, like ripgrep's `--pre`.
+#[derive(Clone, Debug)]
+pub struct Preprocessor {
+    command: Option<PathBuf>,
+    globs: Override,
+}
+
+impl Default for Preprocessor {
+    fn default() -> Self {
+        Self {
+            command: None,
+            globs: Override::empty(),
+        }
+    }
+}
+
+impl Preprocessor {
+    pub fn new(command: Option<PathBuf>, globs: Vec<String>) -> Result<Self> {
+        let mut builder = OverrideBuilder::new(std::env::current_dir()?);
+        for glob in globs {
+            builder.add(&glob)?;
+        }
+
+        Ok(Self {
+            command,
+            globs: builder.build()?,
+        })
+    }
+
+    /// Whether the file should be preprocessed, which is every file unless `--pre-glob` is given.
+    pub fn applies_to(&self, path: &Path) -> bool {
+        self.command.is_some() && !self.globs.matched(path, false).is_ignore()
+    }
+
+    /// Spawns the command with the file path as its argument and file contents on stdin.
+    pub(crate) fn reader(&self, path: &Path) -> io::Result<CommandReader> {
+        let command = self.command.as_ref().ok_or(io::ErrorKind::InvalidInput)?;
+        let mut cmd = Command::new(command);
+        cmd.arg(path).stdin(Stdio::from(File::open(path)?));
+
+        Ok(CommandReaderBuilder::new().build(&mut cmd)?)
+    }
+
+    /// Returns the whole output of the command for the file.
+    pub fn output(&self, path: &Path) -> io::Result<Vec<u8>> {
+        let mut reader = self.reader(path)?;
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        reader.close()?;
+        Ok(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(None, &[], "doc.pdf" => false; "no command")]
+    #[test_case(Some("pdftotext"), &[], "doc.pdf" => true; "no globs")]
+    #[test_case(Some("pdftotext"), &["*.pdf"], "doc.pdf" => true; "matching glob")]
+    #[test_case(Some("pdftotext"), &["*.pdf"], "main.rs" => false; "not matching glob")]
+    fn applies_to(command: Option<&str>, globs: &[&str], path: &str) -> bool {
+        let globs = globs.iter().map(|&glob| glob.to_owned()).collect();
+        Preprocessor::new(command.map(PathBuf::from), globs)
+            .unwrap()
+            .applies_to(Path::new(path))
+    }
+}
@@ -0,0 +1,113 @@
+use std::{
+    io::{self, Read},
+    time::{Duration, Instant},
+};
+
+/// Progress is reported only for files that take longer than this to search, and that often.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Number of bytes already searched in a file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileProgress {
+    pub name: String,
+    pub searched: u64,
+    pub total: u64,
+}
+
+impl FileProgress {
+    pub fn is_done(&self) -> bool {
+        self.searched >= self.total
+    }
+
+    pub fn percent(&self) -> u64 {
+        (self.searched * 100).checked_div(self.total).unwrap_or(100)
+    }
+}
+
+/// Reader counting bytes read from a file, periodically passing the progress to a callback.
+/// Reading is aborted once the callback returns `false`.
+pub(crate) struct ProgressReader<R, F> {
+    inner: R,
+    progress: FileProgress,
+    last_report: Instant,
+    reported: bool,
+    report: F,
+}
+
+impl<R, F> ProgressReader<R, F>
+where
+    R: Read,
+    F: FnMut(&FileProgress) -> bool,
+{
+    pub(crate) fn new(inner: R, name: String, total: u64, report: F) -> Self {
+        Self {
+            inner,
+            progress: FileProgress {
+                name,
+                searched: 0,
+                total,
+            },
+            last_report: Instant::now(),
+            reported: false,
+            report,
+        }
+    }
+
+    /// Reports the file as done, if any progress was reported before.
+    pub(crate) fn finish(mut self) {
+        if self.reported {
+            self.progress.searched = self.progress.total;
+            (self.report)(&self.progress);
+        }
+    }
+}
+
+impl<R, F> Read for ProgressReader<R, F>
+where
+    R: Read,
+    F: FnMut(&FileProgress) -> bool,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.progress.searched = (self.progress.searched + read as u64).min(self.progress.total);
+
+        if self.last_report.elapsed() >= PROGRESS_INTERVAL {
+            self.last_report = Instant::now();
+            self.reported = true;
+            if !(self.report)(&self.progress) {
+                return Err(io::Error::other("search cancelled"));
+            }
+        }
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percent() {
+        let progress = |searched, total| FileProgress {
+            name: "log".into(),
+            searched,
+            total,
+        };
+        assert_eq!(progress(0, 200).percent(), 0);
+        assert_eq!(progress(50, 200).percent(), 25);
+        assert_eq!(progress(0, 0).percent(), 100);
+        assert!(progress(200, 200).is_done());
+    }
+
+    #[test]
+    fn quick_read_is_not_reported() {
+        let mut reports = 0;
+        let mut reader = ProgressReader::new(&b"some text"[..], "log".into(), 9, |_: &_| {
+            reports += 1;
+            true
+        });
+        io::copy(&mut reader, &mut io::sink()).unwrap();
+        reader.finish();
+        assert_eq!(reports, 0);
+    }
+}
@@ -0,0 +1,372 @@
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::{bail, Result};
+use grep::matcher::{Captures, Matcher};
+
+use super::{engine::GrepMatcher, git::git, SearchConfig};
+
+/// Number of unchanged lines shown around changed ones in a patch.
+const DIFF_CONTEXT: usize = 3;
+
+/// Replaces matches with a template, which may refer to capture groups as `$1` or `${name}`.
+pub struct Replacer {
+    matcher: GrepMatcher,
+    template: String,
+}
+
+impl Replacer {
+    pub fn new(config: &SearchConfig, template: String) -> Result<Self> {
+        if config.multiline {
+            bail!("Replacing is not supported in multiline mode");
+        }
+        if config.invert_match {
+            bail!("Replacing is not supported with inverted match");
+        }
+        Ok(Self {
+            matcher: GrepMatcher::new(config)?,
+            template,
+        })
+    }
+
+    pub fn template(&self) -> &str {
+        &self.template
+    }
+
+    /// Line with all matches replaced, or `None` if nothing in it matches.
+    pub fn replace_line(&self, line: &str) -> Option<String> {
+        self.replace(line.as_bytes())
+            .map(|replaced| String::from_utf8_lossy(&replaced).into_owned())
+    }
+
+    fn replace(&self, line: &[u8]) -> Option<Vec<u8>> {
+        match &self.matcher {
+            GrepMatcher::Regex(matcher, _) => replace(matcher, line, self.template.as_bytes()),
+            #[cfg(feature = "pcre2")]
+            GrepMatcher::Pcre2(matcher, _) => replace(matcher, line, self.template.as_bytes()),
+        }
+    }
+
+    /// Replaces matches in lines of a file found by the search, sorted by their numbers.
+    /// Lines changed since the search are left as they are. Only UTF-8 files can be changed,
+    /// like lines shown in the result list.
+    pub fn change_file(&self, path: &Path, lines: &[SearchedLine]) -> io::Result<FileChange> {
+        let old = String::from_utf8(fs::read(path)?).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "only UTF-8 files can be changed",
+            )
+        })?;
+
+        let mut changes = BTreeMap::new();
+        let mut stale_lines = Vec::new();
+        for (index, line) in old.split_inclusive('\n').enumerate() {
+            let number = index as u64 + 1;
+            let Ok(searched) = lines.binary_search_by_key(&number, |searched| searched.number)
+            else {
+                continue;
+            };
+            let content = line.trim_end_matches(['\r', '\n']);
+            if !lines[searched].is_unchanged(content) {
+                stale_lines.push(number);
+            } else if let Some(replaced) = self.replace_line(content) {
+                changes.insert(index, replaced + &line[content.len()..]);
+            }
+        }
+        // lines removed from the end of the file
+        let line_count = old.split_inclusive('\n').count() as u64;
+        stale_lines.extend(
+            lines
+                .iter()
+                .map(|searched| searched.number)
+                .filter(|&number| number > line_count),
+        );
+
+        Ok(FileChange {
+            path: path.to_owned(),
+            old,
+            changes,
+            stale_lines,
+        })
+    }
+}
+
+/// Matched line as it was found by the search.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SearchedLine {
+    pub number: u64,
+    pub text: String,
+    /// Only a part of a line shortened in results is known, `text` is then the part shown
+    /// without ellipses, which is empty for an omitted line.
+    pub shortened: bool,
+}
+
+impl SearchedLine {
+    fn is_unchanged(&self, line: &str) -> bool {
+        if self.shortened {
+            line.contains(&self.text)
+        } else {
+            line == self.text
+        }
+    }
+}
+
+fn replace<M: Matcher>(matcher: &M, line: &[u8], template: &[u8]) -> Option<Vec<u8>> {
+    let mut captures = matcher.new_captures().ok()?;
+    let mut replaced = Vec::with_capacity(line.len());
+    let mut matched = false;
+    matcher
+        .replace_with_captures(line, &mut captures, &mut replaced, |captures, dst| {
+            matched = true;
+            captures.interpolate(|name| matcher.capture_index(name), line, template, dst);
+            true
+        })
+        .ok()?;
+    matched.then_some(replaced)
+}
+
+/// Content of a file before and after replacing matches.
+pub struct FileChange {
+    path: PathBuf,
+    old: String,
+    /// New content of changed lines, by index of the line.
+    changes: BTreeMap<usize, String>,
+    /// Numbers of lines different from the ones found by the search.
+    stale_lines: Vec<u64>,
+}
+
+impl FileChange {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn changed_lines(&self) -> usize {
+        self.changes.len()
+    }
+
+    pub fn stale_lines(&self) -> &[u64] {
+        &self.stale_lines
+    }
+
+    fn new_content(&self) -> String {
+        self.old
+            .split_inclusive('\n')
+            .enumerate()
+            .map(|(index, line)| self.changes.get(&index).map_or(line, String::as_str))
+            .collect()
+    }
+
+    /// Writes the changed content to the file.
+    pub fn apply(&self) -> io::Result<()> {
+        fs::write(&self.path, self.new_content())
+    }
+
+    /// The change as a unified diff, to be applied with `git apply` or `patch -p1` in `root`,
+    /// which is an absolute path given by [`patch_root`].
+    pub fn unified_diff(&self, root: &Path) -> String {
+        let lines = self.old.split_inclusive('\n').collect::<Vec<_>>();
+        let name = relative_path(&self.path, root);
+        let name = name.to_string_lossy();
+        let mut diff = format!("--- a/{name}\n+++ b/{name}\n");
+
+        // changes closer than twice the context are shown in one hunk
+        let mut hunks: Vec<Vec<usize>> = Vec::new();
+        for &index in self.changes.keys() {
+            match hunks.last_mut() {
+                Some(hunk) if index - hunk[hunk.len() - 1] <= 2 * DIFF_CONTEXT => hunk.push(index),
+                _ => hunks.push(vec![index]),
+            }
+        }
+
+        // difference between numbers of new and old lines before the current hunk
+        let mut offset = 0;
+        for hunk in hunks {
+            let start = hunk[0].saturating_sub(DIFF_CONTEXT);
+            let end = (hunk[hunk.len() - 1] + 1 + DIFF_CONTEXT).min(lines.len());
+            let mut body = String::new();
+            let mut new_count = 0;
+            for (index, line) in lines.iter().enumerate().take(end).skip(start) {
+                match self.changes.get(&index) {
+                    Some(new) => {
+                        push_diff_lines(&mut body, '-', line);
+                        new_count += push_diff_lines(&mut body, '+', new);
+                    }
+                    None => new_count += push_diff_lines(&mut body, ' ', line),
+                }
+            }
+            let old_count = end - start;
+            let new_start = (start + 1) as isize + offset;
+            diff.push_str(&format!(
+                "@@ -{},{old_count} +{new_start},{new_count} @@\n",
+                start + 1
+            ));
+            diff.push_str(&body);
+            offset += new_count as isize - old_count as isize;
+        }
+
+        diff
+    }
+}
+
+/// Directory which paths in a patch are relative to. It is the root of the git repository of
+/// the current directory, as expected by `git apply`, or the current directory outside of one.
+pub fn patch_root() -> io::Result<PathBuf> {
+    let root = match git(Path::new("."), &["rev-parse", "--show-toplevel"]) {
+        Ok(root) => PathBuf::from(root.trim_end()),
+        Err(_) => std::env::current_dir()?,
+    };
+    fs::canonicalize(root)
+}
+
+/// Path of a file relative to `root`, going up from it for files outside of it.
+fn relative_path(path: &Path, root: &Path) -> PathBuf {
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_owned());
+    let common = path
+        .components()
+        .zip(root.components())
+        .take_while(|(lhs, rhs)| lhs == rhs)
+        .count();
+    root.components()
+        .skip(common)
+        .map(|_| Component::ParentDir)
+        .chain(path.components().skip(common))
+        .collect()
+}
+
+/// Appends lines of `text` to a diff, each one with `prefix`, returning number of lines.
+fn push_diff_lines(diff: &mut String, prefix: char, text: &str) -> usize {
+    let mut count = 0;
+    for line in text.split_inclusive('\n') {
+        diff.push(prefix);
+        diff.push_str(line);
+        if !line.ends_with('\n') {
+            diff.push_str("\n\\ No newline at end of file\n");
+        }
+        count += 1;
+    }
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TempDir;
+    use test_case::test_case;
+
+    fn replacer(pattern: &str, template: &str) -> Replacer {
+        let config = SearchConfig::from(vec![pattern.into()], vec![]).unwrap();
+        Replacer::new(&config, template.into()).unwrap()
+    }
+
+    #[test_case(r"(\w+)\(\)", "$1(ctx)", "a() + b()" => Some("a(ctx) + b(ctx)".into()); "numbered")]
+    #[test_case(r"(?P<key>\w+)=(?P<value>\d+)", "${value}=${key}", "port=80" => Some("80=port".into()); "named")]
+    #[test_case("foo", "", "foo bar" => Some(" bar".into()); "removed")]
+    #[test_case("foo", "baz", "bar" => None; "no match")]
+    fn replace_line(pattern: &str, template: &str, line: &str) -> Option<String> {
+        replacer(pattern, template).replace_line(line)
+    }
+
+    #[test]
+    fn multiline_is_not_supported() {
+        let config = SearchConfig::from(vec!["foo".into()], vec![])
+            .unwrap()
+            .multiline(true);
+        assert!(Replacer::new(&config, "bar".into()).is_err());
+    }
+
+    fn searched(number: u64, text: &str) -> SearchedLine {
+        SearchedLine {
+            number,
+            text: text.into(),
+            shortened: false,
+        }
+    }
+
+    #[test]
+    fn change_file() {
+        let dir = TempDir::new("replace");
+        let path = dir.join("a.txt");
+        let old = (1..=12)
+            .map(|n| format!("line {n}\r\n"))
+            .collect::<String>()
+            + "line 13";
+        fs::write(&path, &old).unwrap();
+
+        let change = replacer(r"line (\d+)", "row $1")
+            .change_file(
+                &path,
+                &[
+                    searched(2, "line 2"),
+                    searched(12, "line 12"),
+                    searched(13, "line 13"),
+                ],
+            )
+            .unwrap();
+        assert_eq!(change.changed_lines(), 3);
+        assert!(change.stale_lines().is_empty());
+
+        // headers are relative to the root, even though the path is absolute
+        let root = fs::canonicalize(&*dir).unwrap();
+        let diff = change.unified_diff(&root);
+        assert_eq!(
+            diff,
+            format!(
+                "--- a/a.txt\n+++ b/a.txt\n\
+                 @@ -1,5 +1,5 @@\n line 1\r\n-line 2\r\n+row 2\r\n line 3\r\n line 4\r\n line 5\r\n\
+                 @@ -9,5 +9,5 @@\n line 9\r\n line 10\r\n line 11\r\n-line 12\r\n+row 12\r\n\
+                 -line 13\n\\ No newline at end of file\n+row 13\n\\ No newline at end of file\n"
+            )
+        );
+
+        change.apply().unwrap();
+        let new = fs::read_to_string(&path).unwrap();
+        assert_eq!(
+            new,
+            old.replace("line 2\r", "row 2\r")
+                .replace("line 12", "row 12")
+                .replace("line 13", "row 13")
+        );
+    }
+
+    #[test]
+    fn lines_changed_since_search_are_left_alone() {
+        let dir = TempDir::new("replace");
+        let path = dir.join("a.txt");
+        let found = [
+            searched(1, "foo 1"),
+            searched(2, "foo 2"),
+            SearchedLine {
+                number: 3,
+                text: "foo 3".into(),
+                shortened: true,
+            },
+        ];
+        // a line is inserted above the matches after the search
+        fs::write(&path, "foo 0\nfoo 1\nfoo 2\n").unwrap();
+
+        let change = replacer("foo", "bar").change_file(&path, &found).unwrap();
+        assert_eq!(change.changed_lines(), 0);
+        assert_eq!(change.stale_lines(), [1, 2, 3]);
+
+        fs::write(&path, "foo 1\nfoo 2 changed\nlong foo 3 line\n").unwrap();
+        let change = replacer("foo", "bar").change_file(&path, &found).unwrap();
+        assert_eq!(change.stale_lines(), [2]);
+        change.apply().unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "bar 1\nfoo 2 changed\nlong bar 3 line\n"
+        );
+    }
+
+    #[test_case("/repo/src/a.rs", "/repo" => "src/a.rs"; "inside")]
+    #[test_case("/repo/src/a.rs", "/repo/src/ui" => "../a.rs"; "sibling")]
+    #[test_case("/etc/app.conf", "/repo" => "../etc/app.conf"; "outside")]
+    fn relative_to_root(path: &str, root: &str) -> String {
+        relative_path(Path::new(path), Path::new(root))
+            .to_string_lossy()
+            .into_owned()
+    }
+}
@@ -0,0 +1,308 @@
+use std::{
+    ffi::OsStr,
+    io::{self, BufRead, BufReader, Read, Write},
+    path::{Component, Path, PathBuf},
+    process::Stdio,
+};
+
+use super::{
+    git::{git, git_bytes, git_command},
+    temp_file::write_temp_file,
+    SearchConfig,
+};
+
+/// Separates the commit from the path of a file in it, e.g. `1a2b3c4:src/main.rs`.
+pub const REVISION_SEPARATOR: char = ':';
+
+/// Historical revisions of a repository, searched instead of the working tree.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Revisions {
+    /// All files as they were in the commit.
+    Commit(String),
+    /// Files changed by each commit of the range, as they were after it.
+    LogRange(String),
+}
+
+/// Virtual path of a file in a commit.
+pub fn revision_path(commit: &str, path: &Path) -> String {
+    let path = path.strip_prefix(".").unwrap_or(path);
+    format!("{commit}{REVISION_SEPARATOR}{}", path.display())
+}
+
+/// Splits a virtual path of a file in a commit into the commit and the file path.
+/// Commits are abbreviated SHA-1 or SHA-256 ids. Files of the working tree may have such
+/// names as well, so only paths found by a search of history are to be split.
+pub fn split_revision_path(path: &str) -> Option<(&str, &Path)> {
+    let (commit, file) = path.split_once(REVISION_SEPARATOR)?;
+    let is_commit =
+        (4..=64).contains(&commit.len()) && commit.bytes().all(|byte| byte.is_ascii_hexdigit());
+    (is_commit && !file.is_empty()).then(|| (commit, Path::new(file)))
+}
+
+/// Calls `visit` with the virtual path, size and content of every file of the revisions
+/// under `path`, until it returns false. Contents are read from the local object database,
+/// files are filtered like ones of the working tree would be.
+pub(crate) fn for_each_blob<F>(
+    revisions: &Revisions,
+    path: &Path,
+    config: &SearchConfig,
+    mut visit: F,
+) -> io::Result<()>
+where
+    F: FnMut(&str, u64, &mut dyn Read) -> io::Result<bool>,
+{
+    // the path does not have to exist in the working tree anymore
+    let (base, pathspec) = if path.is_dir() {
+        (path, ".")
+    } else {
+        (
+            path.parent().unwrap_or(Path::new("")),
+            path.file_name().and_then(OsStr::to_str).unwrap_or("."),
+        )
+    };
+    let dir = if base.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        base
+    };
+
+    let commits = match revisions {
+        Revisions::Commit(rev) => {
+            vec![
+                git(dir, &["rev-parse", "--short", &format!("{rev}^{{commit}}")])?
+                    .trim_end()
+                    .to_owned(),
+            ]
+        }
+        Revisions::LogRange(range) => git(dir, &["rev-list", "--abbrev-commit", range, "--"])?
+            .lines()
+            .map(Into::into)
+            .collect(),
+    };
+    let mut blobs = Vec::new();
+    for commit in &commits {
+        // paths are listed relative to `dir`
+        let listing = match revisions {
+            Revisions::Commit(_) => git(dir, &["ls-tree", "-r", "-z", commit, "--", pathspec])?,
+            Revisions::LogRange(_) => git(
+                dir,
+                &[
+                    "diff-tree",
+                    "-r",
+                    "-z",
+                    "--root",
+                    "--relative",
+                    "--no-renames",
+                    "--no-commit-id",
+                    "--diff-filter=d",
+                    commit,
+                    "--",
+                    pathspec,
+                ],
+            )?,
+        };
+        let files = match revisions {
+            Revisions::Commit(_) => parse_tree(&listing),
+            Revisions::LogRange(_) => parse_diff_tree(&listing),
+        };
+        for (mode, id, file) in files {
+            // symbolic links and submodules have no content to search
+            let file_path = base.join(file);
+            if matches!(mode, "100644" | "100755")
+                && is_searched(&file_path, Path::new(file), config)
+            {
+                blobs.push((revision_path(commit, &file_path), id.to_owned()));
+            }
+        }
+    }
+
+    let mut cat_file = git_command(dir)
+        .args(["cat-file", "--batch"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+    let mut input = cat_file.stdin.take().expect("stdin is piped");
+    let mut output = BufReader::new(cat_file.stdout.take().expect("stdout is piped"));
+    let visited = (|| -> io::Result<()> {
+        for (name, id) in &blobs {
+            writeln!(input, "{id}")?;
+            input.flush()?;
+            // `<id> blob <size>` followed by the content and a newline
+            let mut header = String::new();
+            output.read_line(&mut header)?;
+            let size = header
+                .trim_end()
+                .rsplit(' ')
+                .next()
+                .and_then(|size| size.parse::<u64>().ok())
+                .ok_or_else(|| io::Error::other(format!("{name}: {}", header.trim_end())))?;
+
+            let mut content = (&mut output).take(size);
+            let visited = visit(name, size, &mut content);
+            io::copy(&mut content, &mut io::sink())?;
+            output.read_line(&mut String::new())?;
+            if !visited? {
+                break;
+            }
+        }
+        Ok(())
+    })();
+    drop(input);
+    cat_file.wait()?;
+    visited
+}
+
+/// Whether a file would be searched if it were in the working tree, `relative` is its path
+/// below the searched path.
+fn is_searched(path: &Path, relative: &Path, config: &SearchConfig) -> bool {
+    let hidden = relative
+        .components()
+        .any(|component| component.as_os_str().to_string_lossy().starts_with('.'));
+    (config.search_hidden || !hidden)
+        && !config.overrides.matched(path, false).is_ignore()
+        && !config.types.matched(path, false).is_ignore()
+}
+
+/// Mode, object id and path of files listed by `git ls-tree -z`.
+fn parse_tree(listing: &str) -> Vec<(&str, &str, &str)> {
+    listing
+        .split_terminator('\0')
+        .filter_map(|record| {
+            // `<mode> <type> <id>\t<path>`
+            let (info, path) = record.split_once('\t')?;
+            let mut info = info.split(' ');
+            Some((info.next()?, info.nth(1)?, path))
+        })
+        .collect()
+}
+
+/// New mode, new object id and path of files listed by `git diff-tree -z`.
+fn parse_diff_tree(listing: &str) -> Vec<(&str, &str, &str)> {
+    let mut records = listing.split_terminator('\0');
+    let mut files = Vec::new();
+    // `:<old mode> <new mode> <old id> <new id> <status>` followed by the path
+    while let (Some(info), Some(path)) = (records.next(), records.next()) {
+        let info = info.trim_start_matches(':').split(' ').collect::<Vec<_>>();
+        if let [_, mode, _, id, _] = info[..] {
+            files.push((mode, id, path));
+        }
+    }
+    files
+}
+
+/// Reads the content of a file in a commit given by its virtual path.
+pub fn read_blob(path: &str) -> io::Result<Vec<u8>> {
+    let (commit, file) = split_revision_path(path)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "not a file in a commit"))?;
+    // the file may be gone from the working tree, so git is run in its closest directory left
+    let dir = file
+        .ancestors()
+        .skip(1)
+        .find(|dir| dir.is_dir())
+        .unwrap_or(Path::new("."));
+    let relative = file.strip_prefix(dir).unwrap_or(file);
+    git_bytes(
+        dir,
+        &[
+            "cat-file",
+            "blob",
+            &format!("{commit}:./{}", relative.display()),
+        ],
+    )
+}
+
+/// Writes a file in a commit given by its virtual path to a read-only temporary file,
+/// so that it can be opened in an editor. Returns path of the written file.
+pub fn extract_blob(path: &str) -> io::Result<PathBuf> {
+    let (commit, file) = split_revision_path(path)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "not a file in a commit"))?;
+    // files of repositories in different directories are kept apart
+    let file = std::env::current_dir()?.join(file);
+    let mut target = PathBuf::from(commit);
+    target.extend(
+        file.components()
+            .filter(|component| matches!(component, Component::Normal(_))),
+    );
+    write_temp_file(&target, &read_blob(path)?, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::GitRepo;
+    use std::fs;
+    use test_case::test_case;
+
+    #[test_case("1a2b3c4:src/main.rs" => Some(("1a2b3c4", "src/main.rs")))]
+    #[test_case("1a2b3c4:../repo/a:b.txt" => Some(("1a2b3c4", "../repo/a:b.txt")))]
+    #[test_case(
+        "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08:a.rs"
+            => Some(("9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08", "a.rs"));
+        "sha256"
+    )]
+    #[test_case("src:main.rs" => None; "not a commit")]
+    #[test_case("C:/src/main.rs" => None; "windows drive")]
+    #[test_case("1a2b3c4:" => None; "no path")]
+    fn split(path: &str) -> Option<(&str, &str)> {
+        split_revision_path(path).map(|(commit, file)| (commit, file.to_str().unwrap()))
+    }
+
+    #[test]
+    fn blobs_of_commit_and_log_range() {
+        let dir = GitRepo::new("revision");
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(dir.join("src/a.rs"), "first\n").unwrap();
+        fs::write(dir.join(".hidden"), "first\n").unwrap();
+        let first = dir.commit("first");
+        fs::write(dir.join("src/a.rs"), "second\n").unwrap();
+        fs::write(dir.join("src/b.rs"), "second\n").unwrap();
+        let second = dir.commit("second");
+        fs::remove_dir_all(dir.join("src")).unwrap();
+        dir.commit("third");
+
+        let config = SearchConfig::from(vec![], vec![]).unwrap();
+        let blobs = |revisions: Revisions, path: &Path| {
+            let mut blobs = Vec::new();
+            for_each_blob(&revisions, path, &config, |name, _, reader| {
+                let mut content = String::new();
+                reader.read_to_string(&mut content)?;
+                blobs.push((name.to_owned(), content));
+                Ok(true)
+            })
+            .unwrap();
+            blobs
+        };
+        let name = |commit: &str, file: &str| revision_path(commit, &dir.join(file));
+
+        assert_eq!(
+            blobs(Revisions::Commit(first.clone()), &dir),
+            [(name(&first, "src/a.rs"), "first\n".to_owned())]
+        );
+        assert_eq!(
+            blobs(
+                Revisions::LogRange(format!("{first}..HEAD")),
+                &dir.join("src")
+            ),
+            [
+                (name(&second, "src/a.rs"), "second\n".to_owned()),
+                (name(&second, "src/b.rs"), "second\n".to_owned())
+            ]
+        );
+
+        let b = name(&second, "src/b.rs");
+        assert_eq!(read_blob(&b).unwrap(), b"second\n");
+        let extracted = extract_blob(&b).unwrap();
+        assert_eq!(fs::read(&extracted).unwrap(), b"second\n");
+        assert!(fs::metadata(&extracted).unwrap().permissions().readonly());
+        assert!(extracted.ends_with(
+            Path::new(&second)
+                .join(dir.strip_prefix("/").unwrap())
+                .join("src/b.rs")
+        ));
+        // written again once opened for the second time
+        assert_eq!(extract_blob(&b).unwrap(), extracted);
+
+        fs::remove_file(extracted).unwrap();
+    }
+}
@@ -5,11 +5,17 @@ use ignore::{
 };
 use std::path::PathBuf;
 
+use super::{
+    engine::GrepMatcher, git::GitScope, revision::Revisions, Engine, FileEncoding, Preprocessor,
+    StdinBuffer,
+};
 use crate::ui::cmd_parse::SearchCmd;
 
 #[derive(Clone)]
 pub struct SearchConfig {
-    pub pattern: String,
+    pub patterns: Vec<String>,
+    pub engine: Engine,
+    pub encoding: FileEncoding,
     pub paths: Vec<PathBuf>,
     pub case_insensitive: bool,
     pub case_smart: bool,
@@ -18,13 +24,32 @@ pub struct SearchConfig {
     pub search_hidden: bool,
     pub follow_links: bool,
     pub word_regexp: bool,
+    pub multiline: bool,
+    pub fixed_strings: bool,
+    pub line_regexp: bool,
+    pub invert_match: bool,
+    pub binary: bool,
+    pub text: bool,
+    pub search_zip: bool,
+    pub archives: bool,
+    pub preprocessor: Preprocessor,
+    pub max_depth: Option<usize>,
+    pub max_filesize: Option<u64>,
+    pub max_count: Option<u64>,
+    pub max_columns: Option<usize>,
+    pub max_columns_preview: bool,
     pub after_context: usize,
     pub before_context: usize,
+    pub git_scope: GitScope,
+    /// Searched in place of the working tree.
+    pub revisions: Option<Revisions>,
+    /// Searched in place of the `-` path.
+    pub stdin: Option<StdinBuffer>,
 }
 
 impl SearchConfig {
     pub fn update_from(&mut self, cmd: SearchCmd) {
-        self.pattern = cmd.pattern;
+        self.patterns = cmd.patterns;
         if let Some(globs) = cmd.golb {
             if !globs.is_empty() {
                 let mut builder = OverrideBuilder::new(std::env::current_dir().unwrap());
@@ -38,15 +63,38 @@ impl SearchConfig {
         }
         self.after_context = cmd.after_context;
         self.before_context = cmd.before_context;
+        self.max_count = cmd.max_count;
+        self.max_depth = cmd.max_depth;
+        self.max_filesize = cmd.max_filesize;
     }
 
-    pub fn from(pattern: String, paths: Vec<PathBuf>) -> Result<Self> {
+    /// Search command equivalent to this configuration, globs are not included.
+    pub fn to_cmd(&self) -> SearchCmd {
+        SearchCmd {
+            patterns: self.patterns.clone(),
+            golb: None,
+            before_context: self.before_context,
+            after_context: self.after_context,
+            max_count: self.max_count,
+            max_depth: self.max_depth,
+            max_filesize: self.max_filesize,
+        }
+    }
+
+    /// Checks that the patterns compile, the error points at the invalid part of a pattern.
+    pub fn validate(&self) -> Result<()> {
+        GrepMatcher::new(self).map(|_| ())
+    }
+
+    pub fn from(patterns: Vec<String>, paths: Vec<PathBuf>) -> Result<Self> {
         let mut builder = TypesBuilder::new();
         builder.add_defaults();
         let types = builder.build()?;
 
         Ok(Self {
-            pattern,
+            patterns,
+            engine: Engine::default(),
+            encoding: FileEncoding::default(),
             paths,
             case_insensitive: false,
             case_smart: false,
@@ -55,11 +103,38 @@ impl SearchConfig {
             search_hidden: false,
             follow_links: false,
             word_regexp: false,
+            multiline: false,
+            fixed_strings: false,
+            line_regexp: false,
+            invert_match: false,
+            binary: false,
+            text: false,
+            search_zip: false,
+            archives: false,
+            preprocessor: Preprocessor::default(),
+            max_depth: None,
+            max_filesize: None,
+            max_count: None,
+            max_columns: None,
+            max_columns_preview: false,
             after_context: 0,
             before_context: 0,
+            git_scope: GitScope::default(),
+            revisions: None,
+            stdin: None,
         })
     }
 
+    pub fn engine(mut self, engine: Engine) -> Self {
+        self.engine = engine;
+        self
+    }
+
+    pub fn encoding(mut self, encoding: FileEncoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
     pub fn case_insensitive(mut self, case_insensitive: bool) -> Self {
         self.case_insensitive = case_insensitive;
         self
@@ -110,4 +185,99 @@ impl SearchConfig {
         self.word_regexp = word_regexp;
         self
     }
+
+    pub fn multiline(mut self, multiline: bool) -> Self {
+        self.multiline = multiline;
+        self
+    }
+
+    pub fn fixed_strings(mut self, fixed_strings: bool) -> Self {
+        self.fixed_strings = fixed_strings;
+        self
+    }
+
+    pub fn line_regexp(mut self, line_regexp: bool) -> Self {
+        self.line_regexp = line_regexp;
+        self
+    }
+
+    pub fn invert_match(mut self, invert_match: bool) -> Self {
+        self.invert_match = invert_match;
+        self
+    }
+
+    pub fn binary(mut self, binary: bool) -> Self {
+        self.binary = binary;
+        self
+    }
+
+    pub fn text(mut self, text: bool) -> Self {
+        self.text = text;
+        self
+    }
+
+    pub fn search_zip(mut self, search_zip: bool) -> Self {
+        self.search_zip = search_zip;
+        self
+    }
+
+    pub fn archives(mut self, archives: bool) -> Self {
+        self.archives = archives;
+        self
+    }
+
+    pub fn preprocessor(mut self, preprocessor: Preprocessor) -> Self {
+        self.preprocessor = preprocessor;
+        self
+    }
+
+    pub fn max_depth(mut self, max_depth: Option<usize>) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    pub fn max_filesize(mut self, max_filesize: Option<u64>) -> Self {
+        self.max_filesize = max_filesize;
+        self
+    }
+
+    pub fn max_count(mut self, max_count: Option<u64>) -> Self {
+        self.max_count = max_count;
+        self
+    }
+
+    pub fn max_columns(mut self, max_columns: Option<usize>) -> Self {
+        self.max_columns = max_columns;
+        self
+    }
+
+    pub fn max_columns_preview(mut self, max_columns_preview: bool) -> Self {
+        self.max_columns_preview = max_columns_preview;
+        self
+    }
+
+    pub fn git_scope(mut self, git_scope: GitScope) -> Self {
+        self.git_scope = git_scope;
+        self
+    }
+
+    pub fn revisions(mut self, revisions: Option<Revisions>) -> Self {
+        self.revisions = revisions;
+        self
+    }
+
+    pub fn after_context(mut self, after_context: usize) -> Self {
+        self.after_context = after_context;
+        self
+    }
+
+    pub fn before_context(mut self, before_context: usize) -> Self {
+        self.before_context = before_context;
+        self
+    }
+
+    pub fn stdin(mut self, stdin: Option<StdinBuffer>) -> Self {
+        self.stdin = stdin;
+        self
+    }
 }
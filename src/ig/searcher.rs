@@ -1,19 +1,62 @@
-use super::{file_entry::FileEntry, sink::MatchesSink, SearchConfig};
+use super::{
+    archive,
+    engine::GrepMatcher,
+    file_entry::{EntryType, FileEntry},
+    git::GitFilter,
+    progress::{FileProgress, ProgressReader},
+    revision,
+    sink::MatchesSink,
+    stdin::StdinReader,
+    FileEncoding, SearchConfig, STDIN_NAME,
+};
+use encoding_rs::Encoding;
 use grep::{
-    matcher::LineTerminator,
-    regex::RegexMatcherBuilder,
-    searcher::{BinaryDetection, SearcherBuilder},
+    cli::DecompressionReaderBuilder,
+    matcher::{LineTerminator, Matcher},
+    searcher::{BinaryDetection, Searcher, SearcherBuilder},
 };
 use ignore::WalkBuilder;
-use std::{path::Path, sync::mpsc};
+use std::{
+    fs::{File, Metadata},
+    io,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc,
+    },
+    time::Duration,
+};
 
+/// Searcher events, each tagged with the generation of the search that produced it.
 pub enum Event {
-    NewEntry(FileEntry),
-    SearchingFinished,
-    Error,
+    /// Matches of a file, big files are sent in several batches.
+    NewEntry(u64, FileEntry),
+    Progress(u64, FileProgress),
+    /// Error which does not stop the search, e.g. unreadable file.
+    Diagnostic(u64, String),
+    SearchingFinished(u64),
+}
+
+/// Handle used to stop a running search.
+#[derive(Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
 }
 
-pub fn search(config: SearchConfig, tx: mpsc::Sender<Event>) {
+pub fn search(
+    config: SearchConfig,
+    generation: u64,
+    cancel_token: CancelToken,
+    tx: mpsc::Sender<Event>,
+) {
     std::thread::spawn(move || {
         let path_searchers = config
             .paths
@@ -21,39 +64,174 @@ pub fn search(config: SearchConfig, tx: mpsc::Sender<Event>) {
             .into_iter()
             .map(|path| {
                 let config = config.clone();
+                let cancel_token = cancel_token.clone();
                 let tx = tx.clone();
-                std::thread::spawn(move || run(&path, config, tx))
+                let name = path.to_string_lossy().into_owned();
+                let searcher =
+                    std::thread::spawn(move || run(&path, config, generation, cancel_token, tx));
+                (name, searcher)
             })
             .collect::<Vec<_>>();
 
-        for searcher in path_searchers {
-            if searcher.join().is_err() {
-                tx.send(Event::Error).ok();
-                return;
+        // a failure in one path must not stop searching the others
+        for (name, searcher) in path_searchers {
+            if let Err(panic) = searcher.join() {
+                let message = panic
+                    .downcast_ref::<&str>()
+                    .map(|message| message.to_string())
+                    .or_else(|| panic.downcast_ref::<String>().cloned())
+                    .unwrap_or_else(|| "unknown error".into());
+                tx.send(Event::Diagnostic(
+                    generation,
+                    format!("{name}: search failed: {message}"),
+                ))
+                .ok();
             }
         }
 
-        tx.send(Event::SearchingFinished).ok();
+        tx.send(Event::SearchingFinished(generation)).ok();
     });
 }
 
-fn run(path: &Path, config: SearchConfig, tx: mpsc::Sender<Event>) {
-    let grep_searcher = SearcherBuilder::new()
-        .binary_detection(BinaryDetection::quit(b'\x00'))
+fn run(
+    path: &Path,
+    config: SearchConfig,
+    generation: u64,
+    cancel_token: CancelToken,
+    tx: mpsc::Sender<Event>,
+) {
+    let matcher = match GrepMatcher::new(&config) {
+        Ok(matcher) => matcher,
+        Err(error) => {
+            tx.send(Event::Diagnostic(
+                generation,
+                format!("Invalid pattern: {error}"),
+            ))
+            .ok();
+            return;
+        }
+    };
+    match matcher {
+        GrepMatcher::Regex(matcher, pattern_matchers) => walk(
+            path,
+            config,
+            matcher,
+            pattern_matchers,
+            generation,
+            cancel_token,
+            tx,
+        ),
+        #[cfg(feature = "pcre2")]
+        GrepMatcher::Pcre2(matcher, pattern_matchers) => walk(
+            path,
+            config,
+            matcher,
+            pattern_matchers,
+            generation,
+            cancel_token,
+            tx,
+        ),
+    }
+}
+
+fn walk<M>(
+    path: &Path,
+    config: SearchConfig,
+    matcher: M,
+    pattern_matchers: Vec<M>,
+    generation: u64,
+    cancel_token: CancelToken,
+    tx: mpsc::Sender<Event>,
+) where
+    M: Matcher + Clone + Send + Sync,
+{
+    let mut searcher_builder = SearcherBuilder::new();
+    searcher_builder
+        .binary_detection(if config.text {
+            BinaryDetection::none()
+        } else if config.binary {
+            BinaryDetection::convert(b'\x00')
+        } else {
+            BinaryDetection::quit(b'\x00')
+        })
         .line_terminator(LineTerminator::byte(b'\n'))
         .line_number(true)
         .after_context(config.after_context)
         .before_context(config.before_context)
-        .multi_line(false)
-        .build();
+        .multi_line(config.multiline)
+        .invert_match(config.invert_match)
+        .encoding(config.encoding.searcher_encoding());
+    let grep_searcher = searcher_builder.build();
+
+    if let Some(stdin) = config.stdin.as_ref().filter(|_| path == Path::new("-")) {
+        search_stdin(
+            stdin.reader(cancel_token),
+            &searcher_builder,
+            &matcher,
+            &pattern_matchers,
+            &config,
+            generation,
+            &tx,
+        );
+        return;
+    }
 
-    let matcher = RegexMatcherBuilder::new()
-        .line_terminator(Some(b'\n'))
-        .case_insensitive(config.case_insensitive)
-        .case_smart(config.case_smart)
-        .word(config.word_regexp)
-        .build(&config.pattern)
-        .expect("Cannot build RegexMatcher");
+    if let Some(revisions) = &config.revisions {
+        let mut grep_searcher = grep_searcher;
+        let searched = revision::for_each_blob(revisions, path, &config, |name, size, reader| {
+            if config
+                .max_filesize
+                .is_some_and(|max_filesize| size > max_filesize)
+            {
+                return Ok(true);
+            }
+            let mut sender = FileSender::new(name.into(), None, generation, &tx);
+            let mut send_batch = |entries| sender.send(entries, 0);
+
+            let mut entries = Vec::new();
+            let mut sr = MatchesSink::new(&matcher, &pattern_matchers, &mut entries)
+                .max_count(config.max_count)
+                .max_columns(config.max_columns, config.max_columns_preview)
+                .on_batch(&mut send_batch);
+            if let Err(error) = search_reader(
+                &mut grep_searcher,
+                &searcher_builder,
+                config.encoding,
+                &matcher,
+                reader,
+                &mut sr,
+            ) {
+                tx.send(Event::Diagnostic(generation, format!("{name}: {error}")))
+                    .ok();
+            }
+
+            let skipped_matches = sr.skipped_matches();
+            sender.finish(entries, skipped_matches);
+            Ok(!cancel_token.is_cancelled())
+        });
+        if let Err(error) = searched {
+            tx.send(Event::Diagnostic(
+                generation,
+                format!("{}: {error}", path.display()),
+            ))
+            .ok();
+        }
+        return;
+    }
+
+    let git_filter = match GitFilter::new(&config.git_scope, path) {
+        Ok(git_filter) => git_filter,
+        Err(error) => {
+            tx.send(Event::Diagnostic(
+                generation,
+                format!("{}: {error}", path.display()),
+            ))
+            .ok();
+            return;
+        }
+    };
+
+    let decompression_builder = config.search_zip.then(DecompressionReaderBuilder::new);
 
     let mut builder = WalkBuilder::new(path);
     let walk_parallel = builder
@@ -61,38 +239,292 @@ fn run(path: &Path, config: SearchConfig, tx: mpsc::Sender<Event>) {
         .types(config.types.clone())
         .hidden(!config.search_hidden)
         .follow_links(config.follow_links)
+        .max_depth(config.max_depth)
+        .max_filesize(config.max_filesize)
         .build_parallel();
+    let git_filter = git_filter.as_ref();
 
     walk_parallel.run(move || {
         let tx = tx.clone();
+        let cancel_token = cancel_token.clone();
         let matcher = matcher.clone();
+        let pattern_matchers = pattern_matchers.clone();
         let mut grep_searcher = grep_searcher.clone();
+        let searcher_builder = searcher_builder.clone();
+        let decompression_builder = decompression_builder.clone();
+        let preprocessor = config.preprocessor.clone();
 
         Box::new(move |result| {
+            if cancel_token.is_cancelled() {
+                return ignore::WalkState::Quit;
+            }
+
             let dir_entry = match result {
                 Ok(entry) => {
-                    if !entry.file_type().map_or(false, |ft| ft.is_file()) {
+                    if !entry.file_type().is_some_and(|ft| ft.is_file()) {
                         return ignore::WalkState::Continue;
                     }
                     entry
                 }
-                Err(_) => return ignore::WalkState::Continue,
+                Err(error) => {
+                    tx.send(Event::Diagnostic(generation, error.to_string()))
+                        .ok();
+                    return ignore::WalkState::Continue;
+                }
+            };
+            let path = dir_entry.path();
+            let name = path.to_string_lossy().into_owned();
+            let metadata = dir_entry.metadata().ok();
+            let line_ranges = match git_filter.map(|git_filter| git_filter.lines(path)) {
+                Some(None) => return ignore::WalkState::Continue,
+                Some(line_ranges) => line_ranges,
+                None => None,
             };
-            let mut matches_in_entry = Vec::new();
-            let sr = MatchesSink::new(&matcher, &mut matches_in_entry);
-            grep_searcher
-                .search_path(&matcher, dir_entry.path(), sr)
-                .ok();
 
-            if !matches_in_entry.is_empty() {
-                tx.send(Event::NewEntry(FileEntry::new(
-                    dir_entry.path().to_string_lossy().into_owned(),
-                    matches_in_entry,
-                )))
-                .ok();
+            if config.archives && archive::is_archive(path) {
+                let searched = archive::for_each_member(path, |member, size, reader| {
+                    if config
+                        .max_filesize
+                        .is_some_and(|max_filesize| size > max_filesize)
+                    {
+                        return Ok(true);
+                    }
+                    let name = archive::member_path(&name, member);
+                    let mut sender = FileSender::new(name.clone(), None, generation, &tx);
+                    let mut send_batch = |entries| sender.send(entries, 0);
+
+                    let mut entries = Vec::new();
+                    let mut sr = MatchesSink::new(&matcher, &pattern_matchers, &mut entries)
+                        .max_count(config.max_count)
+                        .max_columns(config.max_columns, config.max_columns_preview)
+                        .on_batch(&mut send_batch);
+                    let mut reader = ProgressReader::new(reader, name.clone(), size, |progress| {
+                        tx.send(Event::Progress(generation, progress.clone())).ok();
+                        !cancel_token.is_cancelled()
+                    });
+                    if let Err(error) = search_reader(
+                        &mut grep_searcher,
+                        &searcher_builder,
+                        config.encoding,
+                        &matcher,
+                        &mut reader,
+                        &mut sr,
+                    ) {
+                        tx.send(Event::Diagnostic(generation, format!("{name}: {error}")))
+                            .ok();
+                    }
+                    reader.finish();
+
+                    let skipped_matches = sr.skipped_matches();
+                    sender.finish(entries, skipped_matches);
+                    Ok(!cancel_token.is_cancelled())
+                });
+                if let Err(error) = searched {
+                    tx.send(Event::Diagnostic(generation, format!("{name}: {error}")))
+                        .ok();
+                }
+                return ignore::WalkState::Continue;
+            }
+
+            let mut sender = FileSender::new(name.clone(), metadata.clone(), generation, &tx);
+            let mut send_batch = |entries| sender.send(entries, 0);
+
+            let mut entries_in_file = Vec::new();
+            let mut sr = MatchesSink::new(&matcher, &pattern_matchers, &mut entries_in_file)
+                .max_count(config.max_count)
+                .max_columns(config.max_columns, config.max_columns_preview)
+                .line_ranges(line_ranges)
+                .on_batch(&mut send_batch);
+
+            let searched = (|| -> io::Result<()> {
+                if preprocessor.applies_to(path) {
+                    let mut reader = preprocessor.reader(path)?;
+                    let searched = search_reader(
+                        &mut grep_searcher,
+                        &searcher_builder,
+                        config.encoding,
+                        &matcher,
+                        &mut reader,
+                        &mut sr,
+                    );
+                    // close explicitly, otherwise its errors would be printed over the UI
+                    searched.and(reader.close())
+                } else if let Some(decompression_builder) = decompression_builder
+                    .as_ref()
+                    .filter(|builder| builder.get_matcher().has_command(path))
+                {
+                    let mut reader = decompression_builder.build(path)?;
+                    let searched = search_reader(
+                        &mut grep_searcher,
+                        &searcher_builder,
+                        config.encoding,
+                        &matcher,
+                        &mut reader,
+                        &mut sr,
+                    );
+                    searched.and(reader.close())
+                } else {
+                    let file = File::open(path)?;
+                    let total = metadata.as_ref().map_or(0, |metadata| metadata.len());
+                    let mut reader = ProgressReader::new(file, name.clone(), total, |progress| {
+                        tx.send(Event::Progress(generation, progress.clone())).ok();
+                        !cancel_token.is_cancelled()
+                    });
+                    let searched = search_reader(
+                        &mut grep_searcher,
+                        &searcher_builder,
+                        config.encoding,
+                        &matcher,
+                        &mut reader,
+                        &mut sr,
+                    );
+                    reader.finish();
+                    searched
+                }
+            })();
+            if let Err(error) = searched {
+                tx.send(Event::Diagnostic(generation, format!("{name}: {error}")))
+                    .ok();
             }
 
+            let skipped_matches = sr.skipped_matches();
+            sender.finish(entries_in_file, skipped_matches);
+
             ignore::WalkState::Continue
         })
     });
 }
+
+/// Searches content of a file, in auto mode transcoded from the encoding guessed from
+/// its beginning, which is read from the already open reader.
+fn search_reader<M, R>(
+    grep_searcher: &mut Searcher,
+    searcher_builder: &SearcherBuilder,
+    encoding: FileEncoding,
+    matcher: &M,
+    reader: R,
+    sink: &mut MatchesSink<M>,
+) -> io::Result<()>
+where
+    M: Matcher,
+    R: io::Read,
+{
+    let (detected, reader) = encoding.detect_reader(reader, true)?;
+    match detected {
+        Some(detected) => {
+            searcher_for(searcher_builder, detected).search_reader(matcher, reader, sink)
+        }
+        None => grep_searcher.search_reader(matcher, reader, sink),
+    }
+}
+
+fn searcher_for(searcher_builder: &SearcherBuilder, encoding: &'static Encoding) -> Searcher {
+    searcher_builder
+        .clone()
+        .encoding(FileEncoding::Fixed(encoding).searcher_encoding())
+        .build()
+}
+
+/// Searches standard input, which may be a stream that never ends, so matches are sent
+/// as soon as they are found instead of in periodic batches.
+fn search_stdin<M>(
+    reader: StdinReader,
+    searcher_builder: &SearcherBuilder,
+    matcher: &M,
+    pattern_matchers: &[M],
+    config: &SearchConfig,
+    generation: u64,
+    tx: &mpsc::Sender<Event>,
+) where
+    M: Matcher,
+{
+    let skipped_lines = reader.skipped_lines();
+    if skipped_lines > 0 {
+        tx.send(Event::Diagnostic(
+            generation,
+            format!("{STDIN_NAME}: first {skipped_lines} lines are no longer kept, they are not searched again"),
+        ))
+        .ok();
+    }
+    let mut sender = FileSender::new(STDIN_NAME.into(), None, generation, tx);
+    let mut send_batch = |entries| sender.send(entries, 0);
+
+    let mut entries = Vec::new();
+    let mut sr = MatchesSink::new(matcher, pattern_matchers, &mut entries)
+        .line_offset(skipped_lines)
+        .max_count(config.max_count)
+        .max_columns(config.max_columns, config.max_columns_preview)
+        .on_batch(&mut send_batch)
+        .batch_interval(Duration::ZERO);
+    // a stream is not waited for to fill the whole detection prefix
+    let searched = config
+        .encoding
+        .detect_reader(reader, false)
+        .and_then(|(detected, reader)| match detected {
+            Some(detected) => {
+                searcher_for(searcher_builder, detected).search_reader(matcher, reader, &mut sr)
+            }
+            None => searcher_builder
+                .build()
+                .search_reader(matcher, reader, &mut sr),
+        });
+    if let Err(error) = searched {
+        tx.send(Event::Diagnostic(
+            generation,
+            format!("{STDIN_NAME}: {error}"),
+        ))
+        .ok();
+    }
+
+    let skipped_matches = sr.skipped_matches();
+    sender.finish(entries, skipped_matches);
+}
+
+/// Sends matches of one file, big files in several batches.
+struct FileSender<'a> {
+    name: String,
+    metadata: Option<Metadata>,
+    generation: u64,
+    tx: &'a mpsc::Sender<Event>,
+    /// Id of the first batch, which the following ones continue.
+    first_id: Option<u64>,
+}
+
+impl<'a> FileSender<'a> {
+    fn new(
+        name: String,
+        metadata: Option<Metadata>,
+        generation: u64,
+        tx: &'a mpsc::Sender<Event>,
+    ) -> Self {
+        Self {
+            name,
+            metadata,
+            generation,
+            tx,
+            first_id: None,
+        }
+    }
+
+    fn send(&mut self, entries: Vec<EntryType>, more_matches: u64) {
+        let mut file_entry = FileEntry::new(self.name.clone(), entries).more_matches(more_matches);
+        if let Some(metadata) = &self.metadata {
+            file_entry = file_entry.metadata(metadata);
+        }
+        match self.first_id {
+            Some(id) => file_entry = file_entry.continued(id),
+            None => self.first_id = Some(file_entry.id()),
+        }
+        self.tx
+            .send(Event::NewEntry(self.generation, file_entry))
+            .ok();
+    }
+
+    /// Sends entries left once the file is searched. If batches have been sent before,
+    /// skipped matches are sent even without entries, to be added to the earlier ones.
+    fn finish(mut self, entries: Vec<EntryType>, skipped_matches: u64) {
+        if !entries.is_empty() || (self.first_id.is_some() && skipped_matches > 0) {
+            self.send(entries, skipped_matches);
+        }
+    }
+}
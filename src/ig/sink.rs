@@ -1,55 +1,458 @@
+use std::{
+    cmp::Reverse,
+    ops::Range,
+    time::{Duration, Instant},
+};
+
 use grep::{
     matcher::Matcher,
-    searcher::{Searcher, Sink, SinkMatch},
+    searcher::{Searcher, Sink, SinkContext, SinkContextKind, SinkMatch},
 };
 
-use super::grep_match::GrepMatch;
+use crate::editor::Column;
+
+use super::{file_entry::EntryType, grep_match::GrepMatch};
+
+/// Collected entries are handed over at least this often while a file is searched...
+const BATCH_INTERVAL: Duration = Duration::from_millis(200);
+/// ...or once there is this many of them, so that huge files do not have to be held in memory.
+const BATCH_SIZE: usize = 10_000;
 
 pub(crate) struct MatchesSink<'a, M>
 where
     M: Matcher,
 {
-    matcher: M,
-    matches_in_entry: &'a mut Vec<GrepMatch>,
+    matcher: &'a M,
+    pattern_matchers: &'a [M],
+    entries: &'a mut Vec<EntryType>,
+    binary_byte_offset: Option<u64>,
+    max_count: Option<u64>,
+    matches_count: u64,
+    max_columns: Option<usize>,
+    max_columns_preview: bool,
+    line_ranges: Option<&'a [Range<u64>]>,
+    /// Context lines since the last match, kept only if they are before a match in `line_ranges`.
+    pending_context: Vec<(u64, String)>,
+    last_match_kept: bool,
+    last_line_number: Option<u64>,
+    line_offset: u64,
+    on_batch: Option<&'a mut dyn FnMut(Vec<EntryType>)>,
+    batch_interval: Duration,
+    last_batch: Instant,
 }
 
 impl<'a, M> MatchesSink<'a, M>
 where
     M: Matcher,
 {
-    pub(crate) fn new(matcher: M, matches_in_entry: &'a mut Vec<GrepMatch>) -> Self {
+    pub(crate) fn new(
+        matcher: &'a M,
+        pattern_matchers: &'a [M],
+        entries: &'a mut Vec<EntryType>,
+    ) -> Self {
         Self {
             matcher,
-            matches_in_entry,
+            pattern_matchers,
+            entries,
+            binary_byte_offset: None,
+            max_count: None,
+            matches_count: 0,
+            max_columns: None,
+            max_columns_preview: false,
+            line_ranges: None,
+            pending_context: Vec::new(),
+            last_match_kept: false,
+            last_line_number: None,
+            line_offset: 0,
+            on_batch: None,
+            batch_interval: BATCH_INTERVAL,
+            last_batch: Instant::now(),
+        }
+    }
+
+    /// Streams entries found so far in batches, instead of keeping all of them until
+    /// the search ends. Entries not handed over yet are left in the entries vector.
+    pub(crate) fn on_batch(mut self, on_batch: &'a mut dyn FnMut(Vec<EntryType>)) -> Self {
+        self.on_batch = Some(on_batch);
+        self
+    }
+
+    /// Changes how often batches are handed over, with zero every entry is handed over
+    /// as soon as it is found.
+    pub(crate) fn batch_interval(mut self, batch_interval: Duration) -> Self {
+        self.batch_interval = batch_interval;
+        self
+    }
+
+    fn flush_batch_if_due(&mut self) {
+        let Some(on_batch) = self.on_batch.as_mut() else {
+            return;
+        };
+        if self.entries.len() >= BATCH_SIZE
+            || (!self.entries.is_empty() && self.last_batch.elapsed() >= self.batch_interval)
+        {
+            on_batch(std::mem::take(self.entries));
+            self.last_batch = Instant::now();
+        }
+    }
+
+    /// Limits number of matches stored per file, the rest of them is only counted.
+    pub(crate) fn max_count(mut self, max_count: Option<u64>) -> Self {
+        self.max_count = max_count;
+        self
+    }
+
+    /// Shortens lines longer than `max_columns` bytes, to a part around the first match
+    /// with `preview`, otherwise to a note that the line is omitted.
+    pub(crate) fn max_columns(mut self, max_columns: Option<usize>, preview: bool) -> Self {
+        self.max_columns = max_columns;
+        self.max_columns_preview = preview;
+        self
+    }
+
+    /// Keeps only matches on lines within `line_ranges`, with their context.
+    pub(crate) fn line_ranges(mut self, line_ranges: Option<&'a [Range<u64>]>) -> Self {
+        self.line_ranges = line_ranges;
+        self
+    }
+
+    /// Adds `line_offset` to line numbers, for input searched from a later line.
+    pub(crate) fn line_offset(mut self, line_offset: u64) -> Self {
+        self.line_offset = line_offset;
+        self
+    }
+
+    /// Whether a match spanning `line_count` lines from `line_number` is on kept lines.
+    fn is_in_line_ranges(&self, line_number: u64, line_count: u64) -> bool {
+        let lines = line_number..line_number + line_count.max(1);
+        self.line_ranges.is_none_or(|ranges| {
+            ranges
+                .iter()
+                .any(|range| range.start < lines.end && lines.start < range.end)
+        })
+    }
+
+    /// Stores an entry of lines starting at `line_number`. When only some lines are kept,
+    /// the searcher's context breaks do not match the gaps left, so these are added here.
+    fn push_entry(
+        &mut self,
+        searcher: &Searcher,
+        entry: EntryType,
+        line_number: u64,
+        line_count: u64,
+    ) {
+        if self.line_ranges.is_some() && searcher.before_context() + searcher.after_context() > 0 {
+            if self
+                .last_line_number
+                .is_some_and(|last| line_number > last + 1)
+            {
+                self.entries.push(EntryType::ContextBreak);
+            }
+            self.last_line_number = Some(line_number + line_count.saturating_sub(1));
         }
+        self.entries.push(entry);
+    }
+
+    /// Line shortened according to `max_columns` together with offsets of matches in it,
+    /// or `None` if it is short enough. Lines of multiline matches are not shortened.
+    fn truncate(&self, text: &str, offsets: &[MatchOffset]) -> Option<(String, Vec<MatchOffset>)> {
+        let max_columns = self
+            .max_columns
+            .filter(|&max_columns| text.len() > max_columns && !text.contains('\n'))?;
+        if self.max_columns_preview {
+            return Some(preview(text, offsets, max_columns));
+        }
+        let note = match offsets.len() {
+            0 => "[Omitted long line]".into(),
+            1 => "[Omitted long line with 1 match]".into(),
+            matches => format!("[Omitted long line with {matches} matches]"),
+        };
+        Some((note, vec![]))
+    }
+
+    pub(crate) fn skipped_matches(&self) -> u64 {
+        self.max_count
+            .map_or(0, |max_count| self.matches_count.saturating_sub(max_count))
+    }
+
+    fn is_limit_reached(&self) -> bool {
+        self.max_count
+            .is_some_and(|max_count| self.matches_count >= max_count)
+    }
+
+    /// Finds matches in `bytes` together with the index of the pattern that produced them.
+    /// Overlapping matches of different patterns are resolved in favour of the earlier one.
+    fn find_offsets(&self, bytes: &[u8]) -> Vec<MatchOffset> {
+        let mut offsets = vec![];
+        if self.pattern_matchers.is_empty() {
+            self.matcher
+                .find_iter(bytes, |m| {
+                    offsets.push((m.start(), m.end(), 0));
+                    true
+                })
+                .ok();
+            return offsets;
+        }
+
+        for (pattern, matcher) in self.pattern_matchers.iter().enumerate() {
+            matcher
+                .find_iter(bytes, |m| {
+                    offsets.push((m.start(), m.end(), pattern));
+                    true
+                })
+                .ok();
+        }
+        offsets.sort_by_key(|&(start, end, pattern)| (start, Reverse(end), pattern));
+
+        let mut last_end = 0;
+        offsets.retain(|&(start, end, _)| {
+            let keep = start >= last_end;
+            if keep {
+                last_end = end;
+            }
+            keep
+        });
+        offsets
     }
 }
 
-impl<'a, M> Sink for MatchesSink<'a, M>
+impl<M> Sink for MatchesSink<'_, M>
 where
     M: Matcher,
 {
     type Error = std::io::Error;
 
-    fn matched(&mut self, _: &Searcher, sink_match: &SinkMatch) -> Result<bool, std::io::Error> {
+    fn matched(
+        &mut self,
+        searcher: &Searcher,
+        sink_match: &SinkMatch,
+    ) -> Result<bool, std::io::Error> {
         let line_number = sink_match
             .line_number()
-            .ok_or(std::io::ErrorKind::InvalidData)?;
-        let text = std::str::from_utf8(sink_match.bytes());
+            .ok_or(std::io::ErrorKind::InvalidData)?
+            + self.line_offset;
+        let line_count = sink_match.lines().count() as u64;
+        self.last_match_kept = self.is_in_line_ranges(line_number, line_count);
+        if !self.last_match_kept {
+            self.pending_context.clear();
+            return Ok(true);
+        }
 
-        let mut offsets = vec![];
-        self.matcher
-            .find_iter(sink_match.bytes(), |m| {
-                offsets.push((m.start(), m.end()));
-                true
-            })
-            .ok();
+        // matches over the limit are only counted
+        self.matches_count += 1;
+        if self.skipped_matches() > 0 {
+            return Ok(true);
+        }
+
+        for (context_line, text) in std::mem::take(&mut self.pending_context) {
+            if context_line + (searcher.before_context() as u64) < line_number {
+                continue;
+            }
+            self.push_entry(
+                searcher,
+                EntryType::Context(context_line, text),
+                context_line,
+                1,
+            );
+        }
+
+        // binary content is not shown, it is enough to know that the file matches
+        if let Some(offset) = self.binary_byte_offset {
+            self.push_entry(
+                searcher,
+                EntryType::BinaryMatch(line_number, offset),
+                line_number,
+                line_count,
+            );
+            return Ok(false);
+        }
+        let bytes = trim_line_terminator(sink_match.bytes());
+        let text = std::str::from_utf8(bytes);
 
-        if let Ok(t) = text {
-            self.matches_in_entry
-                .push(GrepMatch::new(line_number, t.into(), offsets));
+        // inverted matches are lines without any match, so there is nothing to highlight,
+        // offsets would not point at char boundaries of a lossily decoded line either
+        let offsets = if searcher.invert_match() || text.is_err() {
+            vec![]
+        } else {
+            self.find_offsets(sink_match.bytes())
+                .into_iter()
+                .map(|(start, end, pattern)| {
+                    (start.min(bytes.len()), end.min(bytes.len()), pattern)
+                })
+                .collect()
         };
 
+        let text = text.map_or_else(|_| String::from_utf8_lossy(bytes).into_owned(), Into::into);
+        let grep_match = match self.truncate(&text, &offsets) {
+            Some((truncated, truncated_offsets)) => {
+                let column = offsets
+                    .first()
+                    .map_or_else(Column::default, |&(start, ..)| Column::new(&text, start));
+                GrepMatch::new(line_number, truncated, truncated_offsets).truncated(column)
+            }
+            None => GrepMatch::new(line_number, text, offsets),
+        };
+        self.push_entry(searcher, grep_match.into(), line_number, line_count);
+        self.flush_batch_if_due();
+
+        Ok(true)
+    }
+
+    fn context(
+        &mut self,
+        searcher: &Searcher,
+        context: &SinkContext,
+    ) -> Result<bool, std::io::Error> {
+        // with line ranges, context is kept only around kept matches, lines after a dropped
+        // match may still be before the next one
+        let pending = self.line_ranges.is_some()
+            && (*context.kind() == SinkContextKind::Before || !self.last_match_kept);
+        // only lines after the last stored match are kept once the limit is reached
+        if self.is_limit_reached()
+            && (self.skipped_matches() > 0 || *context.kind() != SinkContextKind::After)
+        {
+            return Ok(true);
+        }
+
+        let line_number = context
+            .line_number()
+            .ok_or(std::io::ErrorKind::InvalidData)?
+            + self.line_offset;
+
+        let text = String::from_utf8_lossy(trim_line_terminator(context.bytes()));
+        let text = match self.truncate(&text, &[]) {
+            Some((truncated, _)) => truncated,
+            None => text.into_owned(),
+        };
+        if pending {
+            self.pending_context.push((line_number, text));
+            return Ok(true);
+        }
+        self.push_entry(
+            searcher,
+            EntryType::Context(line_number, text),
+            line_number,
+            1,
+        );
+        self.flush_batch_if_due();
+
+        Ok(true)
+    }
+
+    fn context_break(&mut self, _: &Searcher) -> Result<bool, std::io::Error> {
+        if self.is_limit_reached() || self.line_ranges.is_some() {
+            return Ok(true);
+        }
+        self.entries.push(EntryType::ContextBreak);
+        Ok(true)
+    }
+
+    fn binary_data(
+        &mut self,
+        _: &Searcher,
+        binary_byte_offset: u64,
+    ) -> Result<bool, std::io::Error> {
+        self.binary_byte_offset = Some(binary_byte_offset);
         Ok(true)
     }
 }
+
+/// Start and end byte offsets of a match and index of the pattern that produced it.
+type MatchOffset = (usize, usize, usize);
+
+/// Part of a line around its first match, `max_columns` bytes long, with ellipses where
+/// the line is cut. Offsets of matches starting in the part are moved accordingly.
+fn preview(text: &str, offsets: &[MatchOffset], max_columns: usize) -> (String, Vec<MatchOffset>) {
+    const ELLIPSIS: &str = "…";
+    let floor_char_boundary = |index: usize| {
+        (0..=index)
+            .rev()
+            .find(|&index| text.is_char_boundary(index))
+            .unwrap_or_default()
+    };
+
+    // a quarter of the part is shown before the match
+    let first_match = offsets.first().map_or(0, |&(start, ..)| start);
+    let end = (first_match.saturating_sub(max_columns / 4) + max_columns).min(text.len());
+    let start = floor_char_boundary(end.saturating_sub(max_columns));
+    let end = floor_char_boundary(end);
+
+    let prefix = if start > 0 { ELLIPSIS } else { "" };
+    let suffix = if end < text.len() { ELLIPSIS } else { "" };
+    let shift = |offset: usize| offset - start + prefix.len();
+    let offsets = offsets
+        .iter()
+        .filter(|&&(match_start, match_end, _)| {
+            (start..end).contains(&match_start) || (match_start == end && match_end == end)
+        })
+        .map(|&(match_start, match_end, pattern)| {
+            (shift(match_start), shift(match_end.min(end)), pattern)
+        })
+        .collect();
+
+    (format!("{prefix}{}{suffix}", &text[start..end]), offsets)
+}
+
+fn trim_line_terminator(bytes: &[u8]) -> &[u8] {
+    let bytes = bytes.strip_suffix(b"\n").unwrap_or(bytes);
+    bytes.strip_suffix(b"\r").unwrap_or(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use grep::{regex::RegexMatcher, searcher::SearcherBuilder};
+    use test_case::test_case;
+
+    #[test_case("0123456789abcdef", &[(2, 4, 0)], 8 => ("01234567…".into(), vec![(2, 4, 0)]); "match at start")]
+    #[test_case("0123456789abcdef", &[(10, 12, 0), (15, 16, 1)], 8 => ("…89abcdef".into(), vec![(5, 7, 0), (10, 11, 1)]); "match at end")]
+    #[test_case("0123456789abcdefghij", &[(10, 12, 0), (14, 19, 0), (16, 18, 1)], 8 => ("…89abcdef…".into(), vec![(5, 7, 0), (9, 11, 0)]); "match in the middle")]
+    #[test_case("zażółć gęślą jaźń", &[(12, 16, 0)], 8 => ("… gęśl…".into(), vec![(5, 9, 0)]); "char boundaries")]
+    fn preview_around_first_match(
+        text: &str,
+        offsets: &[MatchOffset],
+        max_columns: usize,
+    ) -> (String, Vec<MatchOffset>) {
+        preview(text, offsets, max_columns)
+    }
+
+    #[test]
+    fn only_matches_in_line_ranges_are_kept_with_their_context() {
+        let matcher = RegexMatcher::new("foo").unwrap();
+        let mut searcher = SearcherBuilder::new()
+            .line_number(true)
+            .before_context(1)
+            .after_context(1)
+            .build();
+        let text = "foo 1\nbar 2\nfoo 3\nbar 4\nbar 5\nbar 6\nfoo 7\nbar 8\nfoo 9\n";
+        let line_ranges = [3..4, 9..10];
+
+        let mut entries = Vec::new();
+        let mut sink =
+            MatchesSink::new(&matcher, &[], &mut entries).line_ranges(Some(&line_ranges));
+        searcher
+            .search_slice(&matcher, text.as_bytes(), &mut sink)
+            .unwrap();
+
+        let lines = entries
+            .iter()
+            .map(|entry| match entry {
+                EntryType::Match(line_number, ..) => format!("match {line_number}"),
+                EntryType::Context(line_number, _) => format!("context {line_number}"),
+                EntryType::ContextBreak => "break".into(),
+                _ => unreachable!(),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            [
+                "context 2",
+                "match 3",
+                "context 4",
+                "break",
+                "context 8",
+                "match 9"
+            ]
+        );
+    }
+}
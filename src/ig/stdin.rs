@@ -0,0 +1,382 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    io::{self, Read},
+    ops::Range,
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+    time::Duration,
+};
+
+use super::searcher::CancelToken;
+
+/// Name under which matches of standard input are listed.
+pub const STDIN_NAME: &str = "<stdin>";
+
+/// How often a reader waiting for more input checks if its search was cancelled.
+const CANCEL_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Input kept to be searched again, older input is dropped once all readers are past it.
+const MAX_KEPT: usize = 64 * 1024 * 1024;
+
+/// Every this many lines the start of a line is indexed, to find lines without scanning
+/// all of the kept input.
+const LINE_INDEX_STEP: u64 = 1024;
+
+/// Standard input read in the background. It can be read only once, so the last
+/// `MAX_KEPT` bytes of it are kept to be searched again with another pattern and shown
+/// in the context viewer.
+#[derive(Clone, Default)]
+pub struct StdinBuffer(Arc<Shared>);
+
+#[derive(Default)]
+struct Shared {
+    content: Mutex<Content>,
+    grown: Condvar,
+    consumed: Condvar,
+}
+
+#[derive(Default)]
+struct Content {
+    bytes: VecDeque<u8>,
+    /// Offset of the first kept byte in the whole input.
+    start: u64,
+    /// Lines dropped completely, the first kept byte is on the line after them.
+    dropped_lines: u64,
+    /// Line terminators read so far.
+    lines: u64,
+    /// Kept line starts of every `LINE_INDEX_STEP`-th line, as line numbers and offsets.
+    line_index: VecDeque<(u64, u64)>,
+    /// Offsets up to which readers have read, by reader ids.
+    readers: HashMap<u64, u64>,
+    next_reader: u64,
+    finished: bool,
+}
+
+/// Kept lines of standard input around a line.
+#[derive(Debug, PartialEq, Eq)]
+pub struct StdinLines {
+    /// Number of the first line, counted from 1.
+    pub first: u64,
+    pub bytes: Vec<u8>,
+    /// False if more of the lines may still be read.
+    pub complete: bool,
+}
+
+impl StdinBuffer {
+    pub fn read_stdin() -> Self {
+        Self::read_from(io::stdin())
+    }
+
+    pub fn read_from(input: impl Read + Send + 'static) -> Self {
+        Self::read_limited(input, MAX_KEPT)
+    }
+
+    fn read_limited(mut input: impl Read + Send + 'static, max_kept: usize) -> Self {
+        let buffer = Self::default();
+        let shared = buffer.0.clone();
+        std::thread::spawn(move || {
+            let mut chunk = vec![0; max_kept.min(64 * 1024)];
+            loop {
+                match input.read(&mut chunk) {
+                    Ok(0) => break,
+                    Ok(read) => {
+                        let mut content = shared.lock();
+                        // a search slower than the input holds reading it back
+                        while !content.drop_read(max_kept - read) {
+                            content = shared
+                                .consumed
+                                .wait(content)
+                                .expect("Stdin reader panicked");
+                        }
+                        content.append(&chunk[..read]);
+                        shared.grown.notify_all();
+                    }
+                    Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(_) => break,
+                }
+            }
+            shared.lock().finished = true;
+            shared.grown.notify_all();
+        });
+        buffer
+    }
+
+    /// Copy of the kept lines within `lines`, the last one only once it is complete or
+    /// the whole input is read.
+    pub fn lines(&self, lines: Range<u64>) -> StdinLines {
+        let content = self.0.lock();
+        let index = content
+            .line_index
+            .partition_point(|&(line, _)| line <= lines.start);
+        let (mut line, offset) = index
+            .checked_sub(1)
+            .map_or((content.dropped_lines + 1, content.start), |index| {
+                content.line_index[index]
+            });
+
+        let mut end = (offset - content.start) as usize;
+        let mut first = None;
+        let mut rest = content.bytes.range(end..);
+        while line < lines.end {
+            if line >= lines.start && first.is_none() {
+                first = Some((line, end));
+            }
+            match rest.position(|&byte| byte == b'\n') {
+                Some(length) => {
+                    end += length + 1;
+                    line += 1;
+                }
+                None => {
+                    if content.finished {
+                        end = content.bytes.len();
+                    }
+                    break;
+                }
+            }
+        }
+
+        let (first, start) = first.unwrap_or((line, end));
+        StdinLines {
+            first,
+            bytes: content.bytes.range(start..end).copied().collect(),
+            complete: line >= lines.end || content.finished,
+        }
+    }
+
+    /// Returns true once the whole input has been read.
+    pub fn is_finished(&self) -> bool {
+        self.0.lock().finished
+    }
+
+    /// Reader of the kept input, which waits for more of it until the input is closed.
+    pub(crate) fn reader(&self, cancel_token: CancelToken) -> StdinReader {
+        let mut content = self.0.lock();
+        let id = content.next_reader;
+        content.next_reader += 1;
+        let position = content.start;
+        content.readers.insert(id, position);
+        StdinReader {
+            buffer: self.clone(),
+            id,
+            position,
+            skipped_lines: content.dropped_lines,
+            cancel_token,
+        }
+    }
+}
+
+impl std::fmt::Debug for StdinBuffer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StdinBuffer").finish_non_exhaustive()
+    }
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, Content> {
+        self.content.lock().expect("Stdin reader panicked")
+    }
+}
+
+impl Content {
+    fn end(&self) -> u64 {
+        self.start + self.bytes.len() as u64
+    }
+
+    fn append(&mut self, chunk: &[u8]) {
+        let end = self.end();
+        for (offset, _) in (end + 1..).zip(chunk).filter(|(_, &byte)| byte == b'\n') {
+            self.lines += 1;
+            if self.lines.is_multiple_of(LINE_INDEX_STEP) {
+                self.line_index.push_back((self.lines + 1, offset));
+            }
+        }
+        self.bytes.extend(chunk);
+    }
+
+    /// Drops the oldest input over `kept` bytes that all readers have read already.
+    /// Returns false if more input is kept, until the readers get past it.
+    fn drop_read(&mut self, kept: usize) -> bool {
+        let excess = self.bytes.len().saturating_sub(kept);
+        let read = self
+            .readers
+            .values()
+            .min()
+            .map_or(u64::MAX, |&position| position - self.start);
+        let dropped = excess.min(usize::try_from(read).unwrap_or(usize::MAX));
+        self.dropped_lines += self
+            .bytes
+            .drain(..dropped)
+            .filter(|&byte| byte == b'\n')
+            .count() as u64;
+        self.start += dropped as u64;
+        while self
+            .line_index
+            .front()
+            .is_some_and(|&(_, offset)| offset < self.start)
+        {
+            self.line_index.pop_front();
+        }
+        self.bytes.len() <= kept
+    }
+}
+
+pub(crate) struct StdinReader {
+    buffer: StdinBuffer,
+    id: u64,
+    position: u64,
+    skipped_lines: u64,
+    cancel_token: CancelToken,
+}
+
+impl StdinReader {
+    /// Lines dropped before the reader was created, which it does not read.
+    pub(crate) fn skipped_lines(&self) -> u64 {
+        self.skipped_lines
+    }
+}
+
+impl Read for StdinReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let shared = &self.buffer.0;
+        let mut content = shared.lock();
+        loop {
+            if self.cancel_token.is_cancelled() {
+                return Err(io::Error::other("search cancelled"));
+            }
+            if content.end() > self.position || content.finished {
+                break;
+            }
+            content = shared
+                .grown
+                .wait_timeout(content, CANCEL_CHECK_INTERVAL)
+                .expect("Stdin reader panicked")
+                .0;
+        }
+
+        // input is not dropped before all readers have read it
+        let available = content
+            .bytes
+            .range((self.position - content.start) as usize..);
+        let read = available.len().min(buf.len());
+        for (target, &byte) in buf.iter_mut().zip(available) {
+            *target = byte;
+        }
+        self.position += read as u64;
+        content.readers.insert(self.id, self.position);
+        shared.consumed.notify_all();
+        Ok(read)
+    }
+}
+
+impl Drop for StdinReader {
+    fn drop(&mut self) {
+        if let Ok(mut content) = self.buffer.0.content.lock() {
+            content.readers.remove(&self.id);
+        }
+        self.buffer.0.consumed.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn reads_whole_input_again() {
+        let buffer = StdinBuffer::read_from(io::Cursor::new(b"first\nsecond\n".to_vec()));
+        for _ in 0..2 {
+            let mut text = String::new();
+            buffer
+                .reader(CancelToken::default())
+                .read_to_string(&mut text)
+                .unwrap();
+            assert_eq!(text, "first\nsecond\n");
+        }
+        assert!(buffer.is_finished());
+        assert_eq!(buffer.lines(2..3).bytes, b"second\n");
+        assert_eq!(buffer.lines(5..10).bytes, b"");
+    }
+
+    fn wait_until_finished(buffer: &StdinBuffer) {
+        while !buffer.is_finished() {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn lines_are_found_by_number() {
+        let input = (1..=3000)
+            .map(|line| format!("{line}\n"))
+            .collect::<String>();
+        let buffer = StdinBuffer::read_from(io::Cursor::new(input.into_bytes()));
+        wait_until_finished(&buffer);
+
+        let lines = buffer.lines(2047..2050);
+        assert_eq!(lines.first, 2047);
+        assert_eq!(lines.bytes, b"2047\n2048\n2049\n");
+        assert!(lines.complete);
+    }
+
+    #[test]
+    fn oldest_input_is_dropped() {
+        let input = (1..=10).map(|line| format!("{line}\n")).collect::<String>();
+        let buffer = StdinBuffer::read_limited(io::Cursor::new(input.into_bytes()), 6);
+        wait_until_finished(&buffer);
+
+        // the end of line 8 is kept
+        assert_eq!(
+            buffer.lines(1..10),
+            StdinLines {
+                first: 8,
+                bytes: b"\n9\n".to_vec(),
+                complete: true,
+            }
+        );
+        let mut reader = buffer.reader(CancelToken::default());
+        assert_eq!(reader.skipped_lines(), 7);
+        let mut text = String::new();
+        reader.read_to_string(&mut text).unwrap();
+        assert_eq!(text, "\n9\n10\n");
+    }
+
+    /// Input handed over in chunks sent through a channel, closed with the channel.
+    struct Chunks(mpsc::Receiver<&'static [u8]>);
+
+    impl Read for Chunks {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let Ok(chunk) = self.0.recv() else {
+                return Ok(0);
+            };
+            buf[..chunk.len()].copy_from_slice(chunk);
+            Ok(chunk.len())
+        }
+    }
+
+    #[test]
+    fn input_is_kept_until_readers_read_it() {
+        let (tx, rx) = mpsc::channel();
+        let buffer = StdinBuffer::read_limited(Chunks(rx), 2);
+        let mut reader = buffer.reader(CancelToken::default());
+        for line in [b"a\n", b"b\n", b"c\n"] {
+            tx.send(line).unwrap();
+        }
+        drop(tx);
+
+        let mut text = String::new();
+        reader.read_to_string(&mut text).unwrap();
+        assert_eq!(text, "a\nb\nc\n");
+        drop(reader);
+        wait_until_finished(&buffer);
+        assert_eq!(buffer.lines(1..10).bytes, b"c\n");
+    }
+
+    #[test]
+    fn waiting_reader_is_cancelled() {
+        // never finished, as if more input was yet to come
+        let buffer = StdinBuffer::default();
+        let cancel_token = CancelToken::default();
+        let mut reader = buffer.reader(cancel_token.clone());
+        std::thread::spawn(move || cancel_token.cancel());
+        assert!(reader.read(&mut [0; 16]).is_err());
+    }
+}
@@ -0,0 +1,98 @@
+use std::{
+    fs::{self, DirBuilder, OpenOptions},
+    io::{self, Write},
+    path::{Component, Path, PathBuf},
+    process,
+    sync::{Mutex, PoisonError},
+};
+
+/// Writes `content` to a new file at `path` below a temporary directory private to the
+/// current process, so that contents which are no files of their own can be opened in
+/// an editor. A file written to the same path before is replaced. Returns path of the file.
+pub(super) fn write_temp_file(path: &Path, content: &[u8], readonly: bool) -> io::Result<PathBuf> {
+    // only plain components are kept, so that a file cannot be written outside the directory
+    let relative = path
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(part) => Some(part),
+            _ => None,
+        })
+        .collect::<PathBuf>();
+    let target = private_dir()?.join(relative);
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    // nobody else can write to the directory, a file left there was written before
+    match fs::remove_file(&target) {
+        Err(error) if error.kind() != io::ErrorKind::NotFound => return Err(error),
+        _ => (),
+    }
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&target)?;
+    file.write_all(content)?;
+    if readonly {
+        let mut permissions = file.metadata()?.permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(&target, permissions)?;
+    }
+    Ok(target)
+}
+
+/// Directory accessible only by the current user, created on first use. A new directory is
+/// always created, so that it cannot be one prepared by somebody else in the shared temp dir.
+fn private_dir() -> io::Result<PathBuf> {
+    static DIR: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+    let mut dir = DIR.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some(dir) = dir.as_ref() {
+        return Ok(dir.clone());
+    }
+
+    let mut builder = DirBuilder::new();
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    for attempt in 0..100 {
+        let path = std::env::temp_dir().join(format!("higrep-{}-{attempt}", process::id()));
+        match builder.create(&path) {
+            Ok(()) => return Ok(dir.insert(path).clone()),
+            Err(error) if error.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(error) => return Err(error),
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        "no temporary directory could be created",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_is_written_to_private_dir() {
+        let path = Path::new("/../tmp/temp-file-test/a.txt");
+        let first = write_temp_file(path, b"first", true).unwrap();
+        assert!(first.starts_with(private_dir().unwrap()));
+        assert!(first.ends_with("tmp/temp-file-test/a.txt"));
+        assert!(fs::metadata(&first).unwrap().permissions().readonly());
+
+        let second = write_temp_file(path, b"second", false).unwrap();
+        assert_eq!(first, second);
+        assert_eq!(fs::read(&second).unwrap(), b"second");
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(private_dir().unwrap())
+                .unwrap()
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o777, 0o700);
+        }
+        fs::remove_file(second).unwrap();
+    }
+}
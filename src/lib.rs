@@ -2,4 +2,6 @@ pub mod app;
 pub mod args;
 pub mod editor;
 pub mod ig;
+#[cfg(test)]
+mod test_utils;
 pub mod ui;
@@ -1,4 +1,4 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use higrep::{
     app::App,
     args::Args,
@@ -9,7 +9,7 @@ use higrep::{
         theme::{dark::Dark, light::Light, Theme, ThemeVariant},
     },
 };
-use std::io::Write;
+use std::{io::Write, path::Path};
 
 fn main() -> Result<()> {
     let args = Args::parse_cli_and_config_file();
@@ -29,20 +29,59 @@ fn main() -> Result<()> {
         return Ok(());
     }
 
-    let paths = if args.paths.is_empty() {
-        vec!["./".into()]
+    let (patterns, paths) = args.patterns_and_paths()?;
+    let paths = if !paths.is_empty() {
+        paths
+    } else if grep::cli::is_readable_stdin() {
+        vec!["-".into()]
     } else {
-        args.paths
+        vec!["./".into()]
     };
+    let stdin = paths
+        .iter()
+        .any(|path| path == Path::new("-"))
+        .then(ig::StdinBuffer::read_stdin);
 
-    let search_config = ig::SearchConfig::from(args.pattern.unwrap(), paths)?
+    let sort = args.sort();
+    let git_scope = args.git_scope();
+    let revisions = args.revisions();
+    let history = revisions.is_some();
+    let preprocessor = ig::Preprocessor::new(args.pre, args.pre_glob)?;
+    let search_config = ig::SearchConfig::from(patterns, paths)?
+        .engine(args.engine)
+        .encoding(args.encoding)
         .case_insensitive(args.ignore_case)
         .case_smart(args.smart_case)
         .search_hidden(args.search_hidden)
         .follow_links(args.follow_links)
         .word_regexp(args.word_regexp)
+        .fixed_strings(args.fixed_strings)
+        .line_regexp(args.line_regexp)
+        .multiline(args.multiline)
+        .invert_match(args.invert_match)
+        .binary(args.binary)
+        .text(args.text)
+        .search_zip(args.search_zip)
+        .archives(args.archives)
+        .preprocessor(preprocessor.clone())
+        .max_depth(args.max_depth)
+        .max_filesize(args.max_filesize)
+        .max_count(args.max_count)
+        .max_columns(args.max_columns)
+        .max_columns_preview(args.max_columns_preview)
+        .after_context(args.after_context.or(args.context).unwrap_or_default())
+        .before_context(args.before_context.or(args.context).unwrap_or_default())
+        .git_scope(git_scope)
+        .revisions(revisions)
         .globs(args.glob)?
-        .file_types(args.type_matching, args.type_not)?;
+        .file_types(args.type_matching, args.type_not)?
+        .stdin(stdin.clone());
+    search_config.validate().context("Invalid pattern")?;
+    let replacer = args
+        .replace
+        .map(|template| ig::Replacer::new(&search_config, template))
+        .transpose()
+        .context("Invalid replacement")?;
 
     let theme: Box<dyn Theme> = match args.theme {
         ThemeVariant::Light => Box::new(Light),
@@ -50,9 +89,13 @@ fn main() -> Result<()> {
     };
     let mut app = App::new(
         search_config,
+        replacer,
         EditorCommand::new(args.editor.custom_command, args.editor.editor)?,
-        ContextViewer::new(args.context_viewer),
+        ContextViewer::new(args.context_viewer, args.encoding, preprocessor, stdin)
+            .text(args.text)
+            .history(history),
         theme,
+        sort,
     );
     app.run()?;
 
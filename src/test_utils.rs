@@ -0,0 +1,92 @@
+//! Fixtures shared by tests of several modules.
+
+use std::{
+    fs,
+    ops::Deref,
+    path::{Path, PathBuf},
+    process::{self, Command},
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+/// Empty directory in the temp dir, removed with its content once dropped, also when
+/// a test fails.
+pub(crate) struct TempDir(PathBuf);
+
+impl TempDir {
+    pub(crate) fn new(name: &str) -> Self {
+        static NEXT: AtomicU32 = AtomicU32::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "higrep-{name}-{}-{}",
+            process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        fs::remove_dir_all(&self.0).ok();
+    }
+}
+
+/// Git repository in a temporary directory.
+pub(crate) struct GitRepo(TempDir);
+
+impl GitRepo {
+    pub(crate) fn new(name: &str) -> Self {
+        let repo = Self(TempDir::new(name));
+        repo.git(&["init", "-q"]);
+        repo
+    }
+
+    /// Output of a successful `git` command run in the repository.
+    pub(crate) fn git(&self, args: &[&str]) -> String {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(&*self.0)
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "git {args:?}: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    /// Commits all files of the working tree, returning the abbreviated id of the commit.
+    pub(crate) fn commit(&self, message: &str) -> String {
+        self.git(&["add", "-A"]);
+        self.git(&[
+            "-c",
+            "user.name=a",
+            "-c",
+            "user.email=a@b",
+            "commit",
+            "-qm",
+            message,
+        ]);
+        self.git(&["rev-parse", "--short", "HEAD"])
+            .trim_end()
+            .to_owned()
+    }
+}
+
+impl Deref for GitRepo {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
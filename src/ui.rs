@@ -1,11 +1,15 @@
 pub mod bottom_bar;
 pub mod cmd_parse;
 pub mod context_viewer;
+pub mod diagnostics_popup;
+pub mod entry_store;
 pub mod input_handler;
 pub mod keymap_popup;
+pub mod replace_popup;
 pub mod result_list;
 pub mod search_popup;
 pub mod soft_warp;
+pub mod sort;
 pub mod theme;
 
 mod scroll_offset_list;
@@ -7,7 +7,7 @@ use ratatui::{
     Frame,
 };
 
-use crate::ig::Ig;
+use crate::ig::{GitScope, Ig, SearchConfig};
 
 use super::{
     input_handler::{InputHandler, InputState},
@@ -20,6 +20,7 @@ pub fn draw(
     area: Rect,
     result_list: &ResultList,
     ig: &Ig,
+    search_config: &SearchConfig,
     input_handler: &InputHandler,
     theme: &dyn Theme,
 ) {
@@ -39,7 +40,7 @@ pub fn draw(
         .split(area);
 
     draw_app_status(frame, hsplit[0], ig, theme);
-    draw_search_result_summary(frame, hsplit[1], ig, result_list, theme);
+    draw_search_result_summary(frame, hsplit[1], ig, search_config, result_list, theme);
     draw_current_input(frame, hsplit[2], input_handler, theme);
     draw_selected_info(frame, hsplit[3], selected_info_text, theme);
 }
@@ -54,6 +55,8 @@ fn draw_app_status(
         ("搜索中..", theme.searching_state_style())
     } else if ig.last_error().is_some() {
         ("错误", theme.error_state_style())
+    } else if ig.is_cancelled() {
+        ("已取消", theme.cancelled_state_style())
     } else {
         ("完成", theme.finished_state_style())
     };
@@ -71,24 +74,36 @@ fn draw_search_result_summary(
     frame: &mut Frame<CrosstermBackend<std::io::Stdout>>,
     area: Rect,
     ig: &Ig,
+    search_config: &SearchConfig,
     result_list: &ResultList,
     theme: &dyn Theme,
 ) {
-    let search_result = Span::raw(if ig.is_searching() {
-        "".into()
+    let mut search_result = if ig.is_searching() {
+        ig.file_progress().map_or_else(String::default, |progress| {
+            format!(
+                " {} {}% ({}/{})",
+                progress.name.trim_start_matches("./"),
+                progress.percent(),
+                human_readable_size(progress.searched),
+                human_readable_size(progress.total),
+            )
+        })
     } else if let Some(err) = ig.last_error() {
         format!(" {err}")
     } else {
         let total_no_of_matches = result_list.get_total_number_of_matches();
-        if total_no_of_matches == 0 {
+        if total_no_of_matches == 0 && search_config.invert_match {
+            " No non-matching lines found.".into()
+        } else if total_no_of_matches == 0 {
             " No matches found.".into()
         } else {
             let no_of_files = result_list.get_total_number_of_file_entries();
 
-            let matches_str = if total_no_of_matches == 1 {
-                "match"
-            } else {
-                "matches"
+            let matches_str = match (search_config.invert_match, total_no_of_matches) {
+                (false, 1) => "match",
+                (false, _) => "matches",
+                (true, 1) => "non-matching line",
+                (true, _) => "non-matching lines",
             };
             let files_str = if no_of_files == 1 { "file" } else { "files" };
 
@@ -101,10 +116,24 @@ fn draw_search_result_summary(
 
             format!(" Found {total_no_of_matches} {matches_str} in {no_of_files} {files_str}{filtered_str}.")
         }
-    });
+    };
+    if search_config.git_scope != GitScope::All {
+        search_result.insert_str(0, &format!(" [{}]", search_config.git_scope));
+    }
+    if ig.is_cancelled() {
+        search_result.push_str(" Search cancelled.");
+    }
+    if let Some(notice) = ig.notice() {
+        search_result.push_str(&format!(" {notice}"));
+    }
+    let errors = ig.diagnostics().len();
+    if errors > 0 {
+        let errors_str = if errors == 1 { "error" } else { "errors" };
+        search_result.push_str(&format!(" {errors} {errors_str}, press e to show."));
+    }
 
     frame.render_widget(
-        Paragraph::new(search_result)
+        Paragraph::new(Span::raw(search_result))
             .style(theme.bottom_bar_style())
             .alignment(Alignment::Left),
         area,
@@ -159,3 +188,31 @@ fn draw_selected_info(
         area,
     );
 }
+
+fn human_readable_size(size: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "K", "M", "G"];
+    let mut size = size as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{size}{}", UNITS[unit])
+    } else {
+        format!("{size:.1}{}", UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(512 => "512B")]
+    #[test_case(1536 => "1.5K")]
+    #[test_case(20 * 1024 * 1024 * 1024 => "20.0G")]
+    fn size(size: u64) -> String {
+        human_readable_size(size)
+    }
+}
@@ -1,19 +1,23 @@
+use itertools::Itertools;
 use lazy_static::lazy_static;
 use regex::Regex;
 
 lazy_static! {
-    static ref AB_RE: Regex = Regex::new("--([ab]?\\d+)([ab]\\d+)?").unwrap();
-    static ref CMD_RE_Q: Regex =
-        Regex::new("\"([^\"]+)\"([^\"-]*)(--([ab]?\\d+)([ab]\\d+)?)?").unwrap();
-    static ref CMD_RE: Regex = Regex::new("([^\" ]+)([^\"-]*)(--([ab]?\\d+)([ab]\\d+)?)?").unwrap();
+    static ref OPTION_RE: Regex = Regex::new("([abmds]?)(\\d+)([KMG]?)").unwrap();
+    static ref CMD_RE_Q: Regex = Regex::new("((?:\"[^\"]+\"\\s*)+)([^\"-]*)(--(.*))?").unwrap();
+    static ref QUOTED_RE: Regex = Regex::new("\"([^\"]+)\"").unwrap();
+    static ref CMD_RE: Regex = Regex::new("([^\" ]+)([^\"-]*)(--(.*))?").unwrap();
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Default, PartialEq)]
 pub struct SearchCmd {
-    pub pattern: String,
+    pub patterns: Vec<String>,
     pub golb: Option<Vec<String>>,
     pub before_context: usize,
     pub after_context: usize,
+    pub max_count: Option<u64>,
+    pub max_depth: Option<usize>,
+    pub max_filesize: Option<u64>,
 }
 
 impl SearchCmd {
@@ -22,22 +26,25 @@ impl SearchCmd {
             let caps;
             if cmd.starts_with('\"') {
                 if CMD_RE_Q.is_match(cmd) {
-                    let Some(caps_re) = CMD_RE_Q.captures(cmd) else {
-                        return None;
-                    };
-                    caps = caps_re;
+                    caps = CMD_RE_Q.captures(cmd)?;
                 } else {
                     return None;
                 }
             } else if CMD_RE.is_match(cmd) {
-                let Some(caps_re) = CMD_RE.captures(cmd) else {
-                    return None;
-                };
-                caps = caps_re;
+                caps = CMD_RE.captures(cmd)?;
             } else {
                 return None;
             }
             let pat = caps.get(1)?;
+            let patterns = if cmd.starts_with('\"') {
+                QUOTED_RE
+                    .captures_iter(pat.as_str())
+                    .filter_map(|quoted| quoted.get(1))
+                    .map(|quoted| quoted.as_str().to_owned())
+                    .collect()
+            } else {
+                vec![pat.as_str().to_owned()]
+            };
             let mut glob_vec = None;
             if let Some(glob) = caps.get(2) {
                 glob_vec = Some(Vec::new());
@@ -49,77 +56,122 @@ impl SearchCmd {
                     glob_vec.as_mut().unwrap().push(g.to_owned());
                 }
             }
-            let mut a = 0;
-            let mut b = 0;
-            if let Some(ar) = caps.get(3) {
-                let arstr = ar.as_str();
-                if let Some(ab) = parse_ab(arstr) {
-                    a = ab.0;
-                    b = ab.1;
-                }
-            }
-            return Some(Self {
-                pattern: pat.as_str().to_owned(),
+            let mut search_cmd = Self {
+                patterns,
                 golb: glob_vec,
-                before_context: b,
-                after_context: a,
-            });
+                ..Default::default()
+            };
+            if let Some(options) = caps.get(4) {
+                search_cmd.parse_options(options.as_str());
+            }
+            Some(search_cmd)
         } else if cmd.is_empty() {
             None
         } else {
             Some(Self {
-                pattern: cmd.to_owned(),
-                before_context: 0,
-                after_context: 0,
-                golb: None,
+                patterns: vec![cmd.to_owned()],
+                ..Default::default()
             })
         }
     }
-}
 
-fn parse_ab(input: &str) -> Option<(usize, usize)> {
-    let ms = AB_RE.captures(input).unwrap();
-    let mut a = 0;
-    let mut b = 0;
-    if let Some(g1) = ms.get(1) {
-        let mstr = g1.as_str();
-        if mstr.starts_with('a') {
-            if let Some(num) = mstr.strip_prefix('a') {
-                a = num.parse::<usize>().unwrap_or_default();
-                // a = usize::from_str_radix(num, 10).unwrap_or_default();
-            }
-        } else if mstr.starts_with('b') {
-            if let Some(num) = mstr.strip_prefix('b') {
-                b = num.parse::<usize>().unwrap_or_default();
-                // b = usize::from_str_radix(num, 10).unwrap_or_default();
+    /// Parses options given after `--`: `a{n}` and `b{n}` set after and before context,
+    /// a bare `{n}` sets both, `m{n}` limits matches per file, `d{n}` limits directory depth
+    /// and `s{n}[K|M|G]` limits file size.
+    fn parse_options(&mut self, input: &str) {
+        for caps in OPTION_RE.captures_iter(input) {
+            let number = caps[2].parse::<u64>().unwrap_or_default();
+            match &caps[1] {
+                "a" => self.after_context = number as usize,
+                "b" => self.before_context = number as usize,
+                "m" => self.max_count = Some(number),
+                "d" => self.max_depth = Some(number as usize),
+                "s" => self.max_filesize = Some(number.saturating_mul(1 << size_shift(&caps[3]))),
+                _ => {
+                    self.after_context = number as usize;
+                    self.before_context = number as usize;
+                }
             }
-        } else {
-            a = mstr.parse::<usize>().unwrap_or_default();
-            // a = usize::from_str_radix(mstr, 10).unwrap_or_default();
-            b = a;
         }
     }
 
-    if let Some(g1) = ms.get(2) {
-        let mstr = g1.as_str();
-        if mstr.starts_with('a') {
-            if let Some(num) = mstr.strip_prefix('a') {
-                a = num.parse::<usize>().unwrap_or_default();
-                // a = usize::from_str_radix(num, 10).unwrap_or_default();
-            }
-        } else if mstr.starts_with('b') {
-            if let Some(num) = mstr.strip_prefix('b') {
-                b = num.parse::<usize>().unwrap_or_default();
-                // b = usize::from_str_radix(num, 10).unwrap_or_default();
-            }
-        } else {
-            a = mstr.parse::<usize>().unwrap_or_default();
-            // a = usize::from_str_radix(mstr, 10).unwrap_or_default();
-            b = a;
+    /// Formats patterns so that they can be parsed back by [`SearchCmd::parse`].
+    pub fn format_patterns(patterns: &[String]) -> String {
+        match patterns {
+            [pattern] if !pattern.contains(' ') && !pattern.contains("--") => pattern.to_owned(),
+            _ => patterns.iter().map(|p| format!("\"{p}\"")).join(" "),
+        }
+    }
+
+    fn format_options(&self) -> String {
+        let mut options = String::new();
+        if self.after_context != 0 {
+            options.push_str(&format!("a{}", self.after_context));
         }
+        if self.before_context != 0 {
+            options.push_str(&format!("b{}", self.before_context));
+        }
+        if let Some(max_count) = self.max_count {
+            options.push_str(&format!("m{max_count}"));
+        }
+        if let Some(max_depth) = self.max_depth {
+            options.push_str(&format!("d{max_depth}"));
+        }
+        if let Some(max_filesize) = self.max_filesize {
+            options.push_str(&format!("s{}", format_filesize(max_filesize)));
+        }
+        options
     }
+}
 
-    Some((a, b))
+impl std::fmt::Display for SearchCmd {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Self::format_patterns(&self.patterns))?;
+        if let Some(globs) = self.golb.as_ref().filter(|globs| !globs.is_empty()) {
+            write!(f, " {}", globs.join(" "))?;
+        }
+        let options = self.format_options();
+        if !options.is_empty() {
+            write!(f, " --{options}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses file size given as a number with an optional K, M or G suffix.
+pub fn parse_filesize(input: &str) -> Result<u64, String> {
+    let (number, suffix) = match input.find(|c: char| !c.is_ascii_digit()) {
+        Some(index) => input.split_at(index),
+        None => (input, ""),
+    };
+    if !matches!(suffix, "" | "K" | "M" | "G") {
+        return Err(format!(
+            "invalid size suffix '{suffix}', expected K, M or G"
+        ));
+    }
+    let number = number.parse::<u64>().map_err(|error| error.to_string())?;
+    number
+        .checked_mul(1 << size_shift(suffix))
+        .ok_or_else(|| format!("size '{input}' is too big"))
+}
+
+fn format_filesize(size: u64) -> String {
+    match ["G", "M", "K"]
+        .into_iter()
+        .find(|&suffix| size != 0 && size.trailing_zeros() >= size_shift(suffix))
+    {
+        Some(suffix) => format!("{}{suffix}", size >> size_shift(suffix)),
+        None => size.to_string(),
+    }
+}
+
+fn size_shift(suffix: &str) -> u32 {
+    match suffix {
+        "K" => 10,
+        "M" => 20,
+        "G" => 30,
+        _ => 0,
+    }
 }
 
 #[cfg(test)]
@@ -153,9 +205,10 @@ mod tests {
     fn test_ab() {
         let text = "\"传输速度\" *.rs *.json --a100b23";
         let ms = CMD_RE_Q.captures(text).unwrap();
-        let ab = ms.get(3).unwrap().as_str();
-        let ab = parse_ab(ab).unwrap();
-        println!("{},{}", ab.0, ab.1);
+        let mut cmd = SearchCmd::default();
+        cmd.parse_options(ms.get(4).unwrap().as_str());
+        println!("{},{}", cmd.after_context, cmd.before_context);
+        assert_eq!((cmd.after_context, cmd.before_context), (100, 23));
     }
 
     #[test]
@@ -163,20 +216,22 @@ mod tests {
         let text = "\"传输速度\" *.rs *.json --a100b23".to_owned();
         let cmd = SearchCmd::parse(&text).unwrap();
         let sc = SearchCmd {
-            pattern: "传输速度".to_owned(),
+            patterns: vec!["传输速度".to_owned()],
             golb: Some(vec!["*.rs".to_owned(), "*.json".to_owned()]),
             before_context: 23,
             after_context: 100,
+            ..Default::default()
         };
         assert_eq!(cmd, sc);
     }
     #[test]
     fn test_cmd_2() {
         let sc = SearchCmd {
-            pattern: "传输-速度".to_owned(),
+            patterns: vec!["传输-速度".to_owned()],
             golb: Some(vec!["*.rs".to_owned(), "*.json".to_owned()]),
             before_context: 23,
             after_context: 100,
+            ..Default::default()
         };
         let text = "传输-速度 *.rs *.json --a100b23".to_owned();
         let cmd = SearchCmd::parse(&text).unwrap();
@@ -186,10 +241,11 @@ mod tests {
     #[test]
     fn test_cmd_3() {
         let sc = SearchCmd {
-            pattern: "传输速度".to_owned(),
+            patterns: vec!["传输速度".to_owned()],
             golb: Some(vec!["*.rs".to_owned(), "*.json".to_owned()]),
             before_context: 100,
             after_context: 100,
+            ..Default::default()
         };
         let text = "传输速度 *.rs *.json --100".to_owned();
         let cmd = SearchCmd::parse(&text).unwrap();
@@ -199,10 +255,11 @@ mod tests {
     #[test]
     fn test_cmd_4() {
         let sc = SearchCmd {
-            pattern: "传输速度".to_owned(),
+            patterns: vec!["传输速度".to_owned()],
             golb: Some(vec!["*.rs".to_owned(), "*.json".to_owned()]),
             before_context: 0,
             after_context: 0,
+            ..Default::default()
         };
         let text = "传输速度 *.rs *.json ".to_owned();
         let cmd = SearchCmd::parse(&text).unwrap();
@@ -212,10 +269,11 @@ mod tests {
     #[test]
     fn test_cmd_5() {
         let sc = SearchCmd {
-            pattern: "传输 速度".to_owned(),
+            patterns: vec!["传输 速度".to_owned()],
             golb: Some(vec!["*.rs".to_owned(), "*.json".to_owned()]),
             before_context: 0,
             after_context: 0,
+            ..Default::default()
         };
         let text = "\"传输 速度\" *.rs *.json ".to_owned();
         let cmd = SearchCmd::parse(&text).unwrap();
@@ -225,10 +283,11 @@ mod tests {
     #[test]
     fn test_cmd_6() {
         let sc = SearchCmd {
-            pattern: "传输-- 速度".to_owned(),
+            patterns: vec!["传输-- 速度".to_owned()],
             golb: Some(Vec::new()),
             before_context: 0,
             after_context: 0,
+            ..Default::default()
         };
         let text = "\"传输-- 速度\"".to_owned();
         let cmd = SearchCmd::parse(&text).unwrap();
@@ -238,10 +297,11 @@ mod tests {
     #[test]
     fn test_cmd_7() {
         let sc = SearchCmd {
-            pattern: "传输--速度".to_owned(),
+            patterns: vec!["传输--速度".to_owned()],
             golb: Some(Vec::new()),
             before_context: 0,
             after_context: 0,
+            ..Default::default()
         };
         let text = "传输--速度".to_owned();
         let cmd = SearchCmd::parse(&text).unwrap();
@@ -251,10 +311,11 @@ mod tests {
     #[test]
     fn test_cmd_8() {
         let sc = SearchCmd {
-            pattern: "传输-- 速度".to_owned(),
+            patterns: vec!["传输-- 速度".to_owned()],
             golb: Some(Vec::new()),
             before_context: 22,
             after_context: 10,
+            ..Default::default()
         };
         let text = "\"传输-- 速度\"--b22a10".to_owned();
         let cmd = SearchCmd::parse(&text).unwrap();
@@ -264,13 +325,83 @@ mod tests {
     #[test]
     fn test_cmd_9() {
         let sc = SearchCmd {
-            pattern: "传输--速度".to_owned(),
+            patterns: vec!["传输--速度".to_owned()],
             golb: Some(Vec::new()),
             before_context: 100,
             after_context: 100,
+            ..Default::default()
         };
         let text = "传输--速度 --100".to_owned();
         let cmd = SearchCmd::parse(&text).unwrap();
         assert_eq!(cmd, sc);
     }
+
+    #[test]
+    fn test_cmd_10() {
+        let sc = SearchCmd {
+            patterns: vec!["传输".to_owned(), "速度 ".to_owned()],
+            golb: Some(vec!["*.rs".to_owned()]),
+            before_context: 2,
+            after_context: 2,
+            ..Default::default()
+        };
+        let text = "\"传输\" \"速度 \" *.rs --2".to_owned();
+        let cmd = SearchCmd::parse(&text).unwrap();
+        assert_eq!(cmd, sc);
+    }
+
+    #[test]
+    fn test_format_patterns() {
+        let single = vec!["传输速度".to_owned()];
+        assert_eq!(SearchCmd::format_patterns(&single), "传输速度");
+
+        let multiple = vec!["传输".to_owned(), "速 度".to_owned()];
+        let text = SearchCmd::format_patterns(&multiple);
+        assert_eq!(text, "\"传输\" \"速 度\"");
+        assert_eq!(SearchCmd::parse(&text).unwrap().patterns, multiple);
+    }
+
+    #[test]
+    fn test_cmd_limits() {
+        let sc = SearchCmd {
+            patterns: vec!["error".to_owned()],
+            golb: Some(vec!["*.log".to_owned()]),
+            after_context: 2,
+            max_count: Some(5),
+            max_depth: Some(3),
+            max_filesize: Some(10 << 20),
+            ..Default::default()
+        };
+        let text = "error *.log --a2m5d3s10M".to_owned();
+        let cmd = SearchCmd::parse(&text).unwrap();
+        assert_eq!(cmd, sc);
+        assert_eq!(SearchCmd::parse(&cmd.to_string()).unwrap(), sc);
+    }
+
+    #[test]
+    fn test_display() {
+        let cmd = SearchCmd {
+            patterns: vec!["传输".to_owned(), "速度".to_owned()],
+            before_context: 1,
+            max_filesize: Some(1500),
+            ..Default::default()
+        };
+        assert_eq!(cmd.to_string(), "\"传输\" \"速度\" --b1s1500");
+
+        let cmd = SearchCmd {
+            patterns: vec!["传输".to_owned()],
+            ..Default::default()
+        };
+        assert_eq!(cmd.to_string(), "传输");
+    }
+
+    #[test]
+    fn test_parse_filesize() {
+        assert_eq!(parse_filesize("100"), Ok(100));
+        assert_eq!(parse_filesize("2K"), Ok(2048));
+        assert_eq!(parse_filesize("3M"), Ok(3 << 20));
+        assert_eq!(parse_filesize("1G"), Ok(1 << 30));
+        assert!(parse_filesize("1T").is_err());
+        assert!(parse_filesize("M").is_err());
+    }
 }
@@ -1,7 +1,6 @@
 use std::{
     borrow::BorrowMut,
     cmp::max,
-    io::BufRead,
     path::{Path, PathBuf},
 };
 
@@ -16,12 +15,16 @@ use ratatui::{
     Frame,
 };
 use syntect::{
-    easy::HighlightFile,
+    easy::HighlightLines,
     highlighting::{self, ThemeSet},
     parsing::SyntaxSet,
+    util::LinesWithEndings,
 };
 
 use super::{result_list::ResultList, theme::Theme};
+use crate::ig::{
+    archive, decompress, revision, FileEncoding, Preprocessor, StdinBuffer, StdinLines, STDIN_NAME,
+};
 
 #[derive(Default, Clone, Debug, PartialEq, Eq, ValueEnum)]
 pub enum ContextViewerPosition {
@@ -35,28 +38,71 @@ pub enum ContextViewerPosition {
 pub struct ContextViewer {
     highlighted_file_path: PathBuf,
     file_highlighted: Vec<Vec<(highlighting::Style, String)>>,
+    /// Number of the first highlighted line, standard input is highlighted only around
+    /// the selected line.
+    first_line: u64,
     syntax_set: SyntaxSet,
     theme_set: ThemeSet,
     position: ContextViewerPosition,
     size: u16,
+    encoding: FileEncoding,
+    preprocessor: Preprocessor,
+    stdin: Option<StdinBuffer>,
+    /// Selected line of standard input and the lines highlighted around it.
+    stdin_window: Option<(u64, StdinLines)>,
+    binary: bool,
+    /// Binary files are shown as text, like with `--text` in search.
+    text: bool,
+    /// Files are read from commits, given by their virtual paths.
+    history: bool,
 }
 
 impl ContextViewer {
     const MIN_SIZE: u16 = 20;
     const MAX_SIZE: u16 = 80;
     const SIZE_CHANGE_DELTA: u16 = 5;
-
-    pub fn new(position: ContextViewerPosition) -> Self {
+    /// Lines of standard input highlighted around the selected one, more than fit on screen.
+    const STDIN_WINDOW: u64 = 200;
+
+    pub fn new(
+        position: ContextViewerPosition,
+        encoding: FileEncoding,
+        preprocessor: Preprocessor,
+        stdin: Option<StdinBuffer>,
+    ) -> Self {
         Self {
             highlighted_file_path: Default::default(),
             file_highlighted: Default::default(),
+            first_line: 1,
             syntax_set: SyntaxSet::load_defaults_newlines(),
             theme_set: highlighting::ThemeSet::load_defaults(),
             position,
             size: 50,
+            encoding,
+            preprocessor,
+            stdin,
+            stdin_window: None,
+            binary: false,
+            text: false,
+            history: false,
         }
     }
 
+    pub fn text(mut self, text: bool) -> Self {
+        self.text = text;
+        self
+    }
+
+    pub fn history(mut self, history: bool) -> Self {
+        self.history = history;
+        self
+    }
+
+    /// Whether decoded content is binary, e.g. UTF-16 is not once its BOM is handled.
+    fn is_binary(&self, text: &str) -> bool {
+        !self.text && text.contains('\0')
+    }
+
     pub fn toggle_vertical(&mut self) {
         match self.position {
             ContextViewerPosition::None => self.position = ContextViewerPosition::Vertical,
@@ -81,33 +127,114 @@ impl ContextViewer {
         self.size = (self.size - Self::SIZE_CHANGE_DELTA).max(Self::MIN_SIZE);
     }
 
-    pub fn update_if_needed(&mut self, file_path: impl AsRef<Path>, theme: &dyn Theme) {
-        if self.position == ContextViewerPosition::None
-            || self.highlighted_file_path == file_path.as_ref()
-        {
+    pub fn update_if_needed(
+        &mut self,
+        file_path: impl AsRef<Path>,
+        line_number: u64,
+        theme: &dyn Theme,
+    ) {
+        if self.position == ContextViewerPosition::None {
+            return;
+        }
+        if file_path.as_ref() == Path::new(STDIN_NAME) {
+            if let Some(stdin) = self.stdin.clone() {
+                self.update_stdin(&stdin, line_number, theme);
+                return;
+            }
+        }
+        if self.highlighted_file_path == file_path.as_ref() {
             return;
         }
 
         self.highlighted_file_path = file_path.as_ref().into();
         self.file_highlighted.clear();
+        self.first_line = 1;
+        self.binary = false;
+
+        // line numbers of matches refer to the preprocessed content, so it is shown instead
+        let bytes = if let Some(member) = file_path
+            .as_ref()
+            .to_str()
+            .filter(|path| archive::split_member_path(path).is_some())
+        {
+            archive::read_member(member)
+        } else if let Some(blob) = file_path
+            .as_ref()
+            .to_str()
+            .filter(|path| self.history && revision::split_revision_path(path).is_some())
+        {
+            revision::read_blob(blob)
+        } else if self.preprocessor.applies_to(file_path.as_ref()) {
+            self.preprocessor.output(file_path.as_ref())
+        } else {
+            decompress::read_decompressed(file_path.as_ref())
+        };
+        let Ok(bytes) = bytes else {
+            return;
+        };
+        let text = self.encoding.decode(&bytes);
+        // binary files are not highlighted, they would only garble the terminal
+        self.binary = self.is_binary(&text);
+        if self.binary {
+            return;
+        }
+
+        self.highlight(&text, file_path.as_ref(), theme);
+    }
 
-        let mut highlighter = HighlightFile::new(
-            file_path,
-            &self.syntax_set,
-            &self.theme_set.themes[theme.context_viewer_theme()],
-        )
-        .expect("Failed to create line highlighter");
-        let mut line = String::new();
-
-        while highlighter
-            .reader
-            .read_line(&mut line)
-            .expect("Not valid UTF-8")
-            > 0
+    /// Standard input may be too long to be highlighted whole and keeps growing while it is
+    /// read, so lines around the selected one are highlighted again once another line is
+    /// selected or more of them are read.
+    fn update_stdin(&mut self, stdin: &StdinBuffer, line_number: u64, theme: &dyn Theme) {
+        if self.highlighted_file_path != Path::new(STDIN_NAME) {
+            self.highlighted_file_path = STDIN_NAME.into();
+            self.stdin_window = None;
+        }
+        let is_selected = |(line, _): &(u64, StdinLines)| *line == line_number;
+        if self
+            .stdin_window
+            .as_ref()
+            .is_some_and(|window| is_selected(window) && window.1.complete)
+        {
+            return;
+        }
+
+        let lines = stdin.lines(
+            line_number.saturating_sub(Self::STDIN_WINDOW / 2)
+                ..line_number + Self::STDIN_WINDOW / 2,
+        );
+        if self
+            .stdin_window
+            .as_ref()
+            .is_some_and(|window| is_selected(window) && window.1 == lines)
         {
+            return;
+        }
+
+        self.file_highlighted.clear();
+        self.first_line = lines.first;
+        let text = self.encoding.decode(&lines.bytes).into_owned();
+        self.binary = self.is_binary(&text);
+        if !self.binary {
+            self.highlight(&text, Path::new(STDIN_NAME), theme);
+        }
+        self.stdin_window = Some((line_number, lines));
+    }
+
+    /// Appends highlighted lines of `text`, using syntax guessed from the file path.
+    fn highlight(&mut self, text: &str, file_path: &Path, theme: &dyn Theme) {
+        let syntax = self
+            .syntax_set
+            .find_syntax_for_file(file_path)
+            .ok()
+            .flatten()
+            .unwrap_or_else(|| self.syntax_set.find_syntax_plain_text());
+        let mut highlighter =
+            HighlightLines::new(syntax, &self.theme_set.themes[theme.context_viewer_theme()]);
+
+        for line in LinesWithEndings::from(text) {
             let regions: Vec<(highlighting::Style, &str)> = highlighter
-                .highlight_lines
-                .highlight_line(&line, &self.syntax_set)
+                .highlight_line(line, &self.syntax_set)
                 .expect("Failed to highlight line");
 
             let span_vec = regions
@@ -116,7 +243,6 @@ impl ContextViewer {
                 .collect();
 
             self.file_highlighted.push(span_vec);
-            line.clear(); // read_line appends so we need to clear between lines
         }
     }
 
@@ -161,7 +287,14 @@ impl ContextViewer {
             .borders(Borders::ALL)
             .border_type(BorderType::Rounded);
 
-        if let Some((_, line_number)) = result_list.get_selected_entry() {
+        if self.binary {
+            let paragraph_widget = Paragraph::new(Span::styled(
+                "Binary file, preview is not available.",
+                theme.context_line_color(),
+            ))
+            .block(block_widget);
+            frame.render_widget(paragraph_widget, area);
+        } else if let Some((_, line_number)) = result_list.get_selected_entry() {
             let height = area.height as u64;
             let first_line_index = line_number.saturating_sub(height / 2);
 
@@ -188,10 +321,11 @@ impl ContextViewer {
         match_index: usize,
         theme: &dyn Theme,
     ) -> Vec<Line<'_>> {
+        let first_line = max(first_line_index, self.first_line as usize);
         let mut styled_spans = self
             .file_highlighted
             .iter()
-            .skip(first_line_index.saturating_sub(1))
+            .skip(first_line - self.first_line as usize)
             .take(height)
             .map(|line| {
                 line.iter()
@@ -208,8 +342,13 @@ impl ContextViewer {
             .map(Line::from)
             .collect_vec();
 
-        let match_offset = match_index - max(first_line_index, 1);
-        let styled_line = &mut styled_spans[match_offset];
+        // standard input may not be highlighted up to the match yet, or not any more
+        let Some(styled_line) = match_index
+            .checked_sub(first_line)
+            .and_then(|match_offset| styled_spans.get_mut(match_offset))
+        else {
+            return styled_spans;
+        };
         let line_width = styled_line.width();
         let span_vec = &mut styled_line.spans;
 
@@ -229,13 +368,19 @@ impl ContextViewer {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{test_utils::TempDir, ui::theme::dark::Dark};
     use test_case::test_case;
 
     #[test_case(ContextViewerPosition::None => ContextViewerPosition::Vertical)]
     #[test_case(ContextViewerPosition::Vertical => ContextViewerPosition::None)]
     #[test_case(ContextViewerPosition::Horizontal => ContextViewerPosition::Vertical)]
     fn toggle_vertical(initial_position: ContextViewerPosition) -> ContextViewerPosition {
-        let mut context_viewer = ContextViewer::new(initial_position);
+        let mut context_viewer = ContextViewer::new(
+            initial_position,
+            FileEncoding::Auto,
+            Preprocessor::default(),
+            None,
+        );
         context_viewer.toggle_vertical();
         context_viewer.position
     }
@@ -244,14 +389,24 @@ mod tests {
     #[test_case(ContextViewerPosition::Vertical => ContextViewerPosition::Horizontal)]
     #[test_case(ContextViewerPosition::Horizontal => ContextViewerPosition::None)]
     fn toggle_horizontal(initial_position: ContextViewerPosition) -> ContextViewerPosition {
-        let mut context_viewer = ContextViewer::new(initial_position);
+        let mut context_viewer = ContextViewer::new(
+            initial_position,
+            FileEncoding::Auto,
+            Preprocessor::default(),
+            None,
+        );
         context_viewer.toggle_horizontal();
         context_viewer.position
     }
 
     #[test]
     fn increase_size() {
-        let mut context_viewer = ContextViewer::new(ContextViewerPosition::None);
+        let mut context_viewer = ContextViewer::new(
+            ContextViewerPosition::None,
+            FileEncoding::Auto,
+            Preprocessor::default(),
+            None,
+        );
         let default_size = context_viewer.size;
         context_viewer.increase_size();
         assert_eq!(
@@ -264,9 +419,44 @@ mod tests {
         assert_eq!(context_viewer.size, ContextViewer::MAX_SIZE);
     }
 
+    #[test]
+    fn binary_is_detected_in_decoded_content() {
+        let dir = TempDir::new("viewer");
+        let utf16 = dir.join("utf16.txt");
+        std::fs::write(&utf16, [0xFF, 0xFE, b'i', 0, b'g', 0]).unwrap();
+        let binary = dir.join("binary.txt");
+        std::fs::write(&binary, b"ig\x00").unwrap();
+        let missing = dir.join("missing.txt");
+
+        let context_viewer = || {
+            ContextViewer::new(
+                ContextViewerPosition::Vertical,
+                FileEncoding::Auto,
+                Preprocessor::default(),
+                None,
+            )
+        };
+        let mut viewer = context_viewer();
+        viewer.update_if_needed(&utf16, 1, &Dark);
+        assert!(!viewer.binary);
+        viewer.update_if_needed(&binary, 1, &Dark);
+        assert!(viewer.binary);
+        viewer.update_if_needed(&missing, 1, &Dark);
+        assert!(!viewer.binary, "flag of the previous file is not kept");
+
+        let mut viewer = context_viewer().text(true);
+        viewer.update_if_needed(&binary, 1, &Dark);
+        assert!(!viewer.binary);
+    }
+
     #[test]
     fn decrease_size() {
-        let mut context_viewer = ContextViewer::new(ContextViewerPosition::None);
+        let mut context_viewer = ContextViewer::new(
+            ContextViewerPosition::None,
+            FileEncoding::Auto,
+            Preprocessor::default(),
+            None,
+        );
         let default_size = context_viewer.size;
         context_viewer.decrease_size();
         assert_eq!(
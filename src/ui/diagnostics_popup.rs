@@ -0,0 +1,92 @@
+use ratatui::{
+    backend::CrosstermBackend,
+    layout::{Alignment, Rect},
+    text::{Line, Span, Text},
+    widgets::{Block, Borders, Clear, Padding, Paragraph, Wrap},
+    Frame,
+};
+
+use super::theme::Theme;
+
+/// Popup listing errors which did not stop the search.
+#[derive(Default)]
+pub struct DiagnosticsPopup {
+    visible: bool,
+    scroll_y: u16,
+}
+
+impl DiagnosticsPopup {
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+        if self.visible {
+            self.scroll_y = 0;
+        }
+    }
+
+    pub fn go_down(&mut self) {
+        self.scroll_y = self.scroll_y.saturating_add(1);
+    }
+
+    pub fn go_up(&mut self) {
+        self.scroll_y = self.scroll_y.saturating_sub(1);
+    }
+
+    pub fn draw(
+        &mut self,
+        frame: &mut Frame<CrosstermBackend<std::io::Stdout>>,
+        diagnostics: &[String],
+        theme: &dyn Theme,
+    ) {
+        if !self.visible {
+            return;
+        }
+
+        let popup_area = Self::get_popup_area(frame.size());
+
+        let max_y = (diagnostics.len() as u16).saturating_sub(popup_area.height.saturating_sub(4));
+        self.scroll_y = self.scroll_y.min(max_y);
+
+        let content = if diagnostics.is_empty() {
+            Text::from("No errors.")
+        } else {
+            Text::from(
+                diagnostics
+                    .iter()
+                    .map(|diagnostic| {
+                        Line::from(Span::styled(diagnostic.as_str(), theme.diagnostic_color()))
+                    })
+                    .collect::<Vec<_>>(),
+            )
+        };
+
+        let paragraph = Paragraph::new(content)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(theme.search_popup_border())
+                    .title(format!(" 错误 ({}) ", diagnostics.len()))
+                    .title_alignment(Alignment::Center)
+                    .padding(Padding::uniform(1)),
+            )
+            .wrap(Wrap { trim: false })
+            .scroll((self.scroll_y, 0));
+
+        frame.render_widget(Clear, popup_area);
+        frame.render_widget(paragraph, popup_area);
+    }
+
+    fn get_popup_area(frame_size: Rect) -> Rect {
+        let height = (frame_size.height as f64 * 0.6) as u16;
+        let y = (frame_size.height - height) / 2;
+
+        let width = (frame_size.width as f64 * 0.8) as u16;
+        let x = (frame_size.width - width) / 2;
+
+        Rect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+}
@@ -0,0 +1,484 @@
+use std::{collections::HashMap, ops::Range};
+
+use crate::{
+    editor::Column,
+    ig::file_entry::{EntryType, FileEntry, FileHeader},
+};
+
+/// Match within a line: start and end byte offsets and index of the pattern.
+pub type MatchSpan = (u32, u32, u32);
+
+/// Matches highlighted in a single line, further ones are shown as plain text.
+/// Keeps the count of spans of a row in 16 bits.
+const MAX_SPANS_PER_LINE: usize = u16::MAX as usize;
+
+/// Row of the result list. Rows are small and fixed size, texts of lines and match spans
+/// of all rows are kept in shared buffers of `EntryStore`.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Row {
+    Header,
+    Match {
+        line_number: u64,
+        text_start: u64,
+        text_len: u32,
+        spans_start: u32,
+        spans_len: u16,
+    },
+    Context {
+        line_number: u64,
+        text_start: u64,
+        text_len: u32,
+    },
+    ContextBreak,
+    BinaryMatch {
+        line_number: u64,
+        offset: u64,
+    },
+}
+
+impl Row {
+    pub(crate) fn is_header(&self) -> bool {
+        matches!(self, Row::Header)
+    }
+
+    pub(crate) fn is_match(&self) -> bool {
+        matches!(self, Row::Match { .. } | Row::BinaryMatch { .. })
+    }
+
+    pub(crate) fn line_number(&self) -> Option<u64> {
+        match *self {
+            Row::Match { line_number, .. }
+            | Row::Context { line_number, .. }
+            | Row::BinaryMatch { line_number, .. } => Some(line_number),
+            Row::Header | Row::ContextBreak => None,
+        }
+    }
+}
+
+/// Borrowed view of a row.
+#[derive(Debug, PartialEq)]
+pub enum Entry<'a> {
+    Header(&'a FileHeader),
+    Match(u64, &'a str, &'a [MatchSpan]),
+    Context(u64, &'a str),
+    ContextBreak,
+    BinaryMatch(u64, u64),
+}
+
+/// Entries of the result list in a compact form. Rows are kept by file, in the order they
+/// were found, the order of files in the list is up to the list.
+#[derive(Default)]
+pub struct EntryStore {
+    /// Rows of each file, starting with its header unless the file is removed.
+    rows: Vec<Vec<Row>>,
+    files: Vec<FileHeader>,
+    /// Files by ids of their entries.
+    file_ids: HashMap<u64, u32>,
+    text: String,
+    spans: Vec<MatchSpan>,
+    /// Bytes of text and number of spans of removed rows, dropped once they take up
+    /// most of the buffers.
+    freed_text: usize,
+    freed_spans: usize,
+    /// Columns of first matches in truncated lines, by start of their text. These cannot be
+    /// computed from the stored part of a line, which is rare enough not to grow every row.
+    truncated_columns: HashMap<u64, Column>,
+}
+
+impl EntryStore {
+    pub(crate) fn get(&self, file: u32, row: Row) -> Entry<'_> {
+        match row {
+            Row::Header => Entry::Header(&self.files[file as usize]),
+            Row::Match {
+                line_number,
+                text_start,
+                text_len,
+                spans_start,
+                spans_len,
+                ..
+            } => {
+                let spans = spans_start as usize..spans_start as usize + spans_len as usize;
+                Entry::Match(
+                    line_number,
+                    self.text(text_start, text_len),
+                    &self.spans[spans],
+                )
+            }
+            Row::Context {
+                line_number,
+                text_start,
+                text_len,
+                ..
+            } => Entry::Context(line_number, self.text(text_start, text_len)),
+            Row::ContextBreak => Entry::ContextBreak,
+            Row::BinaryMatch {
+                line_number,
+                offset,
+            } => Entry::BinaryMatch(line_number, offset),
+        }
+    }
+
+    fn text(&self, start: u64, len: u32) -> &str {
+        &self.text[start as usize..start as usize + len as usize]
+    }
+
+    pub(crate) fn rows(&self, file: u32) -> &[Row] {
+        &self.rows[file as usize]
+    }
+
+    pub fn file(&self, file: u32) -> &FileHeader {
+        &self.files[file as usize]
+    }
+
+    /// Whether the line of `row` was shortened.
+    pub(crate) fn is_shortened(&self, row: Row) -> bool {
+        match row {
+            Row::Match {
+                text_start,
+                text_len,
+                ..
+            } => text_len > 0 && self.truncated_columns.contains_key(&text_start),
+            _ => false,
+        }
+    }
+
+    /// Column of the first match in the whole line of `row`.
+    pub(crate) fn match_column(&self, row: Row) -> Option<Column> {
+        match row {
+            Row::Match {
+                text_start,
+                text_len,
+                spans_start,
+                spans_len,
+                ..
+            } => {
+                let truncated = (text_len > 0)
+                    .then(|| self.truncated_columns.get(&text_start))
+                    .flatten();
+                let first_span = (spans_len > 0).then(|| self.spans[spans_start as usize]);
+                truncated.copied().or_else(|| {
+                    first_span.map(|(start, ..)| {
+                        Column::new(self.text(text_start, text_len), start as usize)
+                    })
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// File stored from entries with `id`.
+    pub fn find_file(&self, id: u64) -> Option<u32> {
+        self.file_ids.get(&id).copied()
+    }
+
+    /// Stores header of a new file, or replaces the header of a file stored before.
+    fn store_header(&mut self, id: u64, header: FileHeader) -> u32 {
+        match self.find_file(id) {
+            Some(file) => {
+                self.files[file as usize] = header;
+                file
+            }
+            None => {
+                let file = u32::try_from(self.files.len()).expect("Too many files");
+                self.file_ids.insert(id, file);
+                self.files.push(header);
+                self.rows.push(vec![Row::Header]);
+                file
+            }
+        }
+    }
+
+    /// Moves texts of entries of a file into shared buffers and appends rows of them to
+    /// the file. Entries start with the header, which replaces the header of a file stored
+    /// before from entries with the same id. Returns the file and the range of its new rows.
+    pub(crate) fn store(&mut self, file_entry: FileEntry) -> (u32, Range<usize>) {
+        let id = file_entry.id();
+        let mut entries = file_entry.get_entries().into_iter();
+        let Some(EntryType::Header(header)) = entries.next() else {
+            unreachable!("Entries of a file start with its header");
+        };
+        let file = self.store_header(id, header);
+
+        let start = self.rows[file as usize].len();
+        for entry in entries {
+            let row = self.store_row(entry);
+            self.rows[file as usize].push(row);
+        }
+        (file, start..self.rows[file as usize].len())
+    }
+
+    fn store_row(&mut self, entry: EntryType) -> Row {
+        match entry {
+            EntryType::Header(_) => unreachable!("A file has a single header"),
+            EntryType::Match(line_number, text, offsets, truncated_column) => {
+                let (text_start, text_len) = self.store_text(&text);
+                let spans_start = u32::try_from(self.spans.len()).expect("Too many matches");
+                // matches past the stored part of a line cannot be highlighted
+                self.spans.extend(
+                    offsets
+                        .into_iter()
+                        .filter(|&(start, ..)| start <= text_len as usize)
+                        .take(MAX_SPANS_PER_LINE)
+                        .map(|(start, end, pattern)| {
+                            (
+                                start as u32,
+                                end.min(text_len as usize) as u32,
+                                u32::try_from(pattern).unwrap_or(u32::MAX),
+                            )
+                        }),
+                );
+                if let Some(column) = truncated_column.filter(|_| text_len > 0) {
+                    self.truncated_columns.insert(text_start, column);
+                }
+                Row::Match {
+                    line_number,
+                    text_start,
+                    text_len,
+                    spans_start,
+                    spans_len: u16::try_from(self.spans.len() - spans_start as usize)
+                        .expect("Spans are limited per line"),
+                }
+            }
+            EntryType::Context(line_number, text) => {
+                let (text_start, text_len) = self.store_text(&text);
+                Row::Context {
+                    line_number,
+                    text_start,
+                    text_len,
+                }
+            }
+            EntryType::ContextBreak => Row::ContextBreak,
+            EntryType::BinaryMatch(line_number, offset) => Row::BinaryMatch {
+                line_number,
+                offset,
+            },
+        }
+    }
+
+    fn store_text(&mut self, text: &str) -> (u64, u32) {
+        // lines are cut at 4 GB, they would not fit on a screen anyway
+        let mut len = text.len().min(u32::MAX as usize);
+        while !text.is_char_boundary(len) {
+            len -= 1;
+        }
+        let start = self.text.len() as u64;
+        self.text.push_str(&text[..len]);
+        (start, u32::try_from(len).expect("Length is limited above"))
+    }
+
+    /// Removes rows of a file and frees their texts, returns the number of removed matches.
+    /// The file is removed with its header.
+    pub(crate) fn remove(&mut self, file: u32, range: Range<usize>) -> usize {
+        let mut matches = 0;
+        for row in self.rows[file as usize].drain(range) {
+            match row {
+                Row::Match {
+                    text_len,
+                    spans_len,
+                    ..
+                } => {
+                    self.freed_text += text_len as usize;
+                    self.freed_spans += spans_len as usize;
+                    matches += 1;
+                }
+                Row::Context { text_len, .. } => self.freed_text += text_len as usize,
+                Row::BinaryMatch { .. } => matches += 1,
+                _ => (),
+            }
+        }
+        if self.freed_text > self.text.len() / 2 || self.freed_spans > self.spans.len() / 2 {
+            self.compact();
+        }
+        matches
+    }
+
+    /// Drops texts and spans of removed rows, moving the ones left to the start of the buffers.
+    fn compact(&mut self) {
+        let text = std::mem::take(&mut self.text);
+        let spans = std::mem::take(&mut self.spans);
+        let truncated_columns = std::mem::take(&mut self.truncated_columns);
+        self.text
+            .reserve(text.len().saturating_sub(self.freed_text));
+        self.spans
+            .reserve(spans.len().saturating_sub(self.freed_spans));
+
+        for row in self.rows.iter_mut().flatten() {
+            match row {
+                Row::Match {
+                    text_start,
+                    text_len,
+                    spans_start,
+                    spans_len,
+                    ..
+                } => {
+                    let start = self.text.len() as u64;
+                    if let Some(&column) = truncated_columns.get(text_start) {
+                        self.truncated_columns.insert(start, column);
+                    }
+                    let old_text = *text_start as usize..*text_start as usize + *text_len as usize;
+                    self.text.push_str(&text[old_text]);
+                    *text_start = start;
+
+                    let old_spans =
+                        *spans_start as usize..*spans_start as usize + *spans_len as usize;
+                    *spans_start = self.spans.len() as u32;
+                    self.spans.extend_from_slice(&spans[old_spans]);
+                }
+                Row::Context {
+                    text_start,
+                    text_len,
+                    ..
+                } => {
+                    let start = self.text.len() as u64;
+                    let old_text = *text_start as usize..*text_start as usize + *text_len as usize;
+                    self.text.push_str(&text[old_text]);
+                    *text_start = start;
+                }
+                _ => (),
+            }
+        }
+        self.freed_text = 0;
+        self.freed_spans = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ig::file_entry::FileEntry;
+
+    fn entries(store: &EntryStore, file: u32) -> Vec<Entry<'_>> {
+        store
+            .rows(file)
+            .iter()
+            .map(|&row| store.get(file, row))
+            .collect()
+    }
+
+    #[test]
+    fn row_is_compact() {
+        assert!(std::mem::size_of::<Row>() <= 32);
+    }
+
+    #[test]
+    fn store_and_get() {
+        let mut store = EntryStore::default();
+        let (file, rows) = store.store(FileEntry::new(
+            "a".into(),
+            vec![
+                EntryType::Context(1, "before".into()),
+                EntryType::Match(2, "some match".into(), vec![(5, 10, 0)], None),
+                EntryType::ContextBreak,
+                EntryType::BinaryMatch(9, 120),
+            ],
+        ));
+
+        assert_eq!(rows, 1..5);
+        let entries = entries(&store, file);
+        assert!(matches!(entries[0], Entry::Header(header) if header.name == "a"));
+        assert_eq!(
+            entries[1..],
+            [
+                Entry::Context(1, "before"),
+                Entry::Match(2, "some match", &[(5, 10, 0)]),
+                Entry::ContextBreak,
+                Entry::BinaryMatch(9, 120)
+            ]
+        );
+        assert_eq!(store.rows(file)[3].line_number(), None);
+    }
+
+    #[test]
+    fn match_column_of_truncated_line() {
+        let mut store = EntryStore::default();
+        let column = Column {
+            byte: Some(5001),
+            char: 5001,
+            display: 5001,
+        };
+        let (file, _) = store.store(FileEntry::new(
+            "a".into(),
+            vec![
+                EntryType::Match(1, "ab foo".into(), vec![(3, 6, 0)], None),
+                EntryType::Match(2, "…x foo…".into(), vec![(5, 8, 0)], Some(column)),
+            ],
+        ));
+
+        let rows = store.rows(file);
+        assert_eq!(
+            store.match_column(rows[1]).and_then(|column| column.byte),
+            Some(4)
+        );
+        assert_eq!(store.match_column(rows[2]), Some(column));
+        assert_eq!(store.match_column(rows[0]), None);
+    }
+
+    #[test]
+    fn spans_past_stored_text_are_dropped() {
+        let mut store = EntryStore::default();
+        let spans = (0..MAX_SPANS_PER_LINE + 10).map(|start| (start, start + 1, 0));
+        let text = "a".repeat(MAX_SPANS_PER_LINE + 10);
+        let (file, _) = store.store(FileEntry::new(
+            "a".into(),
+            vec![
+                EntryType::Match(1, text, spans.collect(), None),
+                EntryType::Match(2, "ab".into(), vec![(1, 5, 300), (4, 6, 0)], None),
+            ],
+        ));
+
+        let entries = entries(&store, file);
+        assert!(
+            matches!(entries[1], Entry::Match(_, _, spans) if spans.len() == MAX_SPANS_PER_LINE)
+        );
+        assert_eq!(entries[2], Entry::Match(2, "ab", &[(1, 2, 300)]));
+    }
+
+    #[test]
+    fn texts_of_removed_rows_are_freed() {
+        let mut store = EntryStore::default();
+        let column = Column {
+            byte: Some(5001),
+            char: 5001,
+            display: 5001,
+        };
+        let (file, _) = store.store(FileEntry::new(
+            "a".into(),
+            vec![
+                EntryType::Match(1, "removed".into(), vec![(0, 7, 0)], None),
+                EntryType::Context(2, "removed too".into()),
+                EntryType::Match(3, "…kept…".into(), vec![(3, 7, 1)], Some(column)),
+            ],
+        ));
+
+        assert_eq!(store.remove(file, 1..3), 1);
+        assert_eq!(store.text, "…kept…");
+        assert_eq!(store.spans, [(3, 7, 1)]);
+        assert_eq!(
+            entries(&store, file)[1],
+            Entry::Match(3, "…kept…", &[(3, 7, 1)])
+        );
+        assert_eq!(store.match_column(store.rows(file)[1]), Some(column));
+    }
+
+    #[test]
+    fn continued_entries_are_appended() {
+        let mut store = EntryStore::default();
+        let entry = FileEntry::new("a".into(), vec![EntryType::Context(1, "first".into())]);
+        let id = entry.id();
+        let (file, _) = store.store(entry);
+        let (file_continued, rows) = store.store(
+            FileEntry::new("a".into(), vec![EntryType::Context(2, "second".into())])
+                .more_matches(3)
+                .continued(id),
+        );
+        assert_eq!(file, file_continued);
+        assert_eq!(rows, 2..3);
+        assert_eq!(store.file(file).more_matches, 3);
+        assert_eq!(store.find_file(id), Some(file));
+        assert_eq!(entries(&store, file)[2], Entry::Context(2, "second"));
+
+        // another file with the same name, e.g. a path given twice
+        let (other, _) = store.store(FileEntry::new::<EntryType>("a".into(), vec![]));
+        assert_ne!(file, other);
+    }
+}
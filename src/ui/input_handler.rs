@@ -29,7 +29,10 @@ pub enum InputMode {
     #[default]
     Normal,
     TextInsertion,
+    Replace,
+    ConfirmReplace,
     Keymap,
+    Diagnostics,
 }
 
 impl InputHandler {
@@ -48,7 +51,12 @@ impl InputHandler {
                     InputMode::TextInsertion => {
                         self.handle_key_in_text_insertion_mode(key_event, app)
                     }
+                    InputMode::Replace => self.handle_key_in_replace_mode(key_event, app),
+                    InputMode::ConfirmReplace => {
+                        self.handle_key_in_confirm_replace_mode(key_event, app)
+                    }
                     InputMode::Keymap => self.handle_key_in_keymap_mode(key_event, app),
+                    InputMode::Diagnostics => self.handle_key_in_diagnostics_mode(key_event, app),
                 }
             }
         }
@@ -90,6 +98,26 @@ impl InputHandler {
                 self.input_mode = InputMode::Normal;
                 app.on_toggle_popup();
             }
+            KeyEvent {
+                code: KeyCode::Char('u'),
+                modifiers: KeyModifiers::CONTROL,
+            } => app.on_toggle_multiline(),
+            KeyEvent {
+                code: KeyCode::Char('f'),
+                modifiers: KeyModifiers::CONTROL,
+            } => app.on_toggle_fixed_strings(),
+            KeyEvent {
+                code: KeyCode::Char('x'),
+                modifiers: KeyModifiers::CONTROL,
+            } => app.on_toggle_line_regexp(),
+            KeyEvent {
+                code: KeyCode::Char('v'),
+                modifiers: KeyModifiers::CONTROL,
+            } => app.on_toggle_invert_match(),
+            KeyEvent {
+                code: KeyCode::Char('t'),
+                modifiers: KeyModifiers::CONTROL,
+            } => app.on_toggle_incremental(),
             KeyEvent {
                 code: KeyCode::Char(c),
                 modifiers: modifier,
@@ -104,18 +132,78 @@ impl InputHandler {
                 code: KeyCode::Backspace,
                 ..
             } => app.on_char_removed(),
+            // an invalid pattern keeps the popup open
             KeyEvent {
                 code: KeyCode::Enter,
                 ..
-            } => {
+            } if app.on_search() => {
                 self.input_mode = InputMode::Normal;
-                app.on_search();
                 app.on_toggle_popup();
             }
             _ => (),
         }
     }
 
+    fn handle_key_in_replace_mode<A: Application>(&mut self, key_event: KeyEvent, app: &mut A) {
+        match key_event {
+            KeyEvent {
+                code: KeyCode::Esc, ..
+            }
+            | KeyEvent {
+                code: KeyCode::Char('c'),
+                modifiers: KeyModifiers::CONTROL,
+            } => {
+                self.input_mode = InputMode::Normal;
+                app.on_toggle_replace_popup();
+            }
+            KeyEvent {
+                code: KeyCode::Char('d'),
+                modifiers: KeyModifiers::CONTROL,
+            } => {
+                self.input_mode = InputMode::Normal;
+                app.on_clear_replacement();
+            }
+            KeyEvent {
+                code: KeyCode::Char(c),
+                modifiers: modifier,
+            } => {
+                if modifier == KeyModifiers::SHIFT {
+                    app.on_replace_char_inserted(c.to_ascii_uppercase());
+                } else if modifier == KeyModifiers::NONE {
+                    app.on_replace_char_inserted(c);
+                }
+            }
+            KeyEvent {
+                code: KeyCode::Backspace,
+                ..
+            } => app.on_replace_char_removed(),
+            // a replacement that cannot be used keeps the popup open
+            KeyEvent {
+                code: KeyCode::Enter,
+                ..
+            } if app.on_set_replacement() => {
+                self.input_mode = InputMode::Normal;
+                app.on_toggle_replace_popup();
+            }
+            _ => (),
+        }
+    }
+
+    fn handle_key_in_confirm_replace_mode<A: Application>(
+        &mut self,
+        key_event: KeyEvent,
+        app: &mut A,
+    ) {
+        self.input_mode = InputMode::Normal;
+        match key_event {
+            KeyEvent {
+                code: KeyCode::Char('y'),
+                modifiers: KeyModifiers::NONE,
+            } => app.on_apply_replacements(),
+            _ => app.on_cancel_replacements(),
+        }
+    }
+
     fn handle_key_in_keymap_mode<A: Application>(&mut self, key_event: KeyEvent, app: &mut A) {
         match key_event {
             KeyEvent {
@@ -156,6 +244,30 @@ impl InputHandler {
         }
     }
 
+    fn handle_key_in_diagnostics_mode<A: Application>(&mut self, key_event: KeyEvent, app: &mut A) {
+        match key_event {
+            KeyEvent {
+                code: KeyCode::Up, ..
+            }
+            | KeyEvent {
+                code: KeyCode::Char('k'),
+                ..
+            } => app.on_diagnostics_up(),
+            KeyEvent {
+                code: KeyCode::Down,
+                ..
+            }
+            | KeyEvent {
+                code: KeyCode::Char('j'),
+                ..
+            } => app.on_diagnostics_down(),
+            _ => {
+                self.input_mode = InputMode::Normal;
+                app.on_toggle_diagnostics();
+            }
+        }
+    }
+
     fn handle_char_input<A: Application>(&mut self, character: char, app: &mut A) {
         self.input_buffer.push(character);
         self.input_state = InputState::Valid;
@@ -197,6 +309,37 @@ impl InputHandler {
             "-" => consume_buffer_and_execute(&mut self.input_buffer, &mut || {
                 app.on_decrease_context_viewer_size()
             }),
+            "c" => {
+                consume_buffer_and_execute(&mut self.input_buffer, &mut || app.on_cancel_search())
+            }
+            "o" => consume_buffer_and_execute(&mut self.input_buffer, &mut || app.on_cycle_sort()),
+            "O" => consume_buffer_and_execute(&mut self.input_buffer, &mut || {
+                app.on_toggle_sort_order()
+            }),
+            "t" => {
+                consume_buffer_and_execute(&mut self.input_buffer, &mut || app.on_cycle_git_scope())
+            }
+            "r" => {
+                self.input_mode = InputMode::Replace;
+                consume_buffer_and_execute(&mut self.input_buffer, &mut || {
+                    app.on_toggle_replace_popup()
+                })
+            }
+            "R" => {
+                self.input_buffer.clear();
+                if app.on_request_replacements() {
+                    self.input_mode = InputMode::ConfirmReplace;
+                }
+            }
+            "P" => {
+                consume_buffer_and_execute(&mut self.input_buffer, &mut || app.on_export_patch())
+            }
+            "e" => {
+                self.input_mode = InputMode::Diagnostics;
+                consume_buffer_and_execute(&mut self.input_buffer, &mut || {
+                    app.on_toggle_diagnostics()
+                })
+            }
             "q" => consume_buffer_and_execute(&mut self.input_buffer, &mut || app.on_exit()),
             "?" => {
                 consume_buffer_and_execute(&mut self.input_buffer, &mut || app.on_toggle_keymap())
@@ -253,12 +396,11 @@ impl InputHandler {
                 self.input_mode = InputMode::TextInsertion;
                 app.on_toggle_popup();
             }
-            KeyCode::Esc => {
+            KeyCode::Esc
                 if matches!(self.input_state, InputState::Valid)
-                    || matches!(self.input_state, InputState::Invalid(_))
-                {
-                    app.on_exit();
-                }
+                    || matches!(self.input_state, InputState::Invalid(_)) =>
+            {
+                app.on_exit();
             }
             _ => (),
         }
@@ -298,6 +440,14 @@ mod tests {
         }
     }
 
+    fn handle_key_text_insertion_mode<A: Application>(key_event: KeyEvent, app: &mut A) {
+        let mut input_handler = InputHandler {
+            input_mode: InputMode::TextInsertion,
+            ..Default::default()
+        };
+        input_handler.handle_key_in_text_insertion_mode(key_event, app);
+    }
+
     fn handle_key_keymap_mode<A: Application>(key_event: KeyEvent, app: &mut A) {
         let mut input_handler = InputHandler {
             input_mode: InputMode::Keymap,
@@ -399,6 +549,37 @@ mod tests {
         handle_key(KeyCode::Char('s'), &mut app_mock);
     }
 
+    #[test]
+    fn cancel_search() {
+        let mut app_mock = MockApplication::default();
+        app_mock.expect_on_cancel_search().once().return_const(());
+        handle_key(KeyCode::Char('c'), &mut app_mock);
+    }
+
+    #[test]
+    fn cycle_sort() {
+        let mut app_mock = MockApplication::default();
+        app_mock.expect_on_cycle_sort().once().return_const(());
+        handle_key(KeyCode::Char('o'), &mut app_mock);
+    }
+
+    #[test]
+    fn toggle_sort_order() {
+        let mut app_mock = MockApplication::default();
+        app_mock
+            .expect_on_toggle_sort_order()
+            .once()
+            .return_const(());
+        handle_key(KeyCode::Char('O'), &mut app_mock);
+    }
+
+    #[test]
+    fn cycle_git_scope() {
+        let mut app_mock = MockApplication::default();
+        app_mock.expect_on_cycle_git_scope().once().return_const(());
+        handle_key(KeyCode::Char('t'), &mut app_mock);
+    }
+
     #[test]
     fn open_file() {
         let mut app_mock = MockApplication::default();
@@ -413,6 +594,242 @@ mod tests {
         handle_key(KeyCode::F(5), &mut app_mock);
     }
 
+    #[test]
+    fn toggle_multiline() {
+        let mut app_mock = MockApplication::default();
+        app_mock
+            .expect_on_toggle_multiline()
+            .once()
+            .return_const(());
+        handle_key_text_insertion_mode(
+            KeyEvent::new(KeyCode::Char('u'), KeyModifiers::CONTROL),
+            &mut app_mock,
+        );
+    }
+
+    #[test]
+    fn toggle_fixed_strings() {
+        let mut app_mock = MockApplication::default();
+        app_mock
+            .expect_on_toggle_fixed_strings()
+            .once()
+            .return_const(());
+        handle_key_text_insertion_mode(
+            KeyEvent::new(KeyCode::Char('f'), KeyModifiers::CONTROL),
+            &mut app_mock,
+        );
+    }
+
+    #[test]
+    fn toggle_line_regexp() {
+        let mut app_mock = MockApplication::default();
+        app_mock
+            .expect_on_toggle_line_regexp()
+            .once()
+            .return_const(());
+        handle_key_text_insertion_mode(
+            KeyEvent::new(KeyCode::Char('x'), KeyModifiers::CONTROL),
+            &mut app_mock,
+        );
+    }
+
+    #[test]
+    fn toggle_invert_match() {
+        let mut app_mock = MockApplication::default();
+        app_mock
+            .expect_on_toggle_invert_match()
+            .once()
+            .return_const(());
+        handle_key_text_insertion_mode(
+            KeyEvent::new(KeyCode::Char('v'), KeyModifiers::CONTROL),
+            &mut app_mock,
+        );
+    }
+
+    #[test]
+    fn toggle_incremental() {
+        let mut app_mock = MockApplication::default();
+        app_mock
+            .expect_on_toggle_incremental()
+            .once()
+            .return_const(());
+        handle_key_text_insertion_mode(
+            KeyEvent::new(KeyCode::Char('t'), KeyModifiers::CONTROL),
+            &mut app_mock,
+        );
+    }
+
+    #[test]
+    fn diagnostics_open() {
+        let mut app_mock = MockApplication::default();
+        app_mock
+            .expect_on_toggle_diagnostics()
+            .once()
+            .return_const(());
+        let mut input_handler = InputHandler::default();
+        handle(&mut input_handler, Char('e'), &mut app_mock);
+        assert_eq!(input_handler.input_mode, InputMode::Diagnostics);
+    }
+
+    #[test_case(KeyEvent::new(KeyCode::Char('e'), KeyModifiers::NONE))]
+    #[test_case(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE))]
+    fn diagnostics_close(event: KeyEvent) {
+        let mut app_mock = MockApplication::default();
+        app_mock
+            .expect_on_toggle_diagnostics()
+            .once()
+            .return_const(());
+        let mut input_handler = InputHandler {
+            input_mode: InputMode::Diagnostics,
+            ..Default::default()
+        };
+        input_handler.handle_key_in_diagnostics_mode(event, &mut app_mock);
+        assert_eq!(input_handler.input_mode, InputMode::Normal);
+    }
+
+    #[test_case(KeyEvent::new(KeyCode::Down, KeyModifiers::NONE))]
+    #[test_case(KeyEvent::new(KeyCode::Char('j'), KeyModifiers::NONE))]
+    fn diagnostics_scroll(event: KeyEvent) {
+        let mut app_mock = MockApplication::default();
+        app_mock
+            .expect_on_diagnostics_down()
+            .once()
+            .return_const(());
+        let mut input_handler = InputHandler {
+            input_mode: InputMode::Diagnostics,
+            ..Default::default()
+        };
+        input_handler.handle_key_in_diagnostics_mode(event, &mut app_mock);
+    }
+
+    #[test_case(true => InputMode::Normal; "valid pattern")]
+    #[test_case(false => InputMode::TextInsertion; "invalid pattern")]
+    fn search_from_popup(valid: bool) -> InputMode {
+        let mut app_mock = MockApplication::default();
+        app_mock.expect_on_search().once().return_const(valid);
+        app_mock
+            .expect_on_toggle_popup()
+            .times(usize::from(valid))
+            .return_const(());
+        let mut input_handler = InputHandler {
+            input_mode: InputMode::TextInsertion,
+            ..Default::default()
+        };
+        input_handler.handle_key_in_text_insertion_mode(
+            KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE),
+            &mut app_mock,
+        );
+        input_handler.input_mode
+    }
+
+    #[test]
+    fn replace_popup_open() {
+        let mut app_mock = MockApplication::default();
+        app_mock
+            .expect_on_toggle_replace_popup()
+            .once()
+            .return_const(());
+        let mut input_handler = InputHandler::default();
+        handle(&mut input_handler, Char('r'), &mut app_mock);
+        assert_eq!(input_handler.input_mode, InputMode::Replace);
+    }
+
+    #[test_case(true => InputMode::Normal; "valid replacement")]
+    #[test_case(false => InputMode::Replace; "invalid replacement")]
+    fn set_replacement(valid: bool) -> InputMode {
+        let mut app_mock = MockApplication::default();
+        app_mock
+            .expect_on_set_replacement()
+            .once()
+            .return_const(valid);
+        app_mock
+            .expect_on_toggle_replace_popup()
+            .times(usize::from(valid))
+            .return_const(());
+        let mut input_handler = InputHandler {
+            input_mode: InputMode::Replace,
+            ..Default::default()
+        };
+        input_handler.handle_key_in_replace_mode(
+            KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE),
+            &mut app_mock,
+        );
+        input_handler.input_mode
+    }
+
+    #[test]
+    fn clear_replacement() {
+        let mut app_mock = MockApplication::default();
+        app_mock
+            .expect_on_clear_replacement()
+            .once()
+            .return_const(());
+        let mut input_handler = InputHandler {
+            input_mode: InputMode::Replace,
+            ..Default::default()
+        };
+        input_handler.handle_key_in_replace_mode(
+            KeyEvent::new(KeyCode::Char('d'), KeyModifiers::CONTROL),
+            &mut app_mock,
+        );
+        assert_eq!(input_handler.input_mode, InputMode::Normal);
+    }
+
+    #[test_case(true => InputMode::ConfirmReplace; "changes to confirm")]
+    #[test_case(false => InputMode::Normal; "nothing to replace")]
+    fn request_replacements(pending: bool) -> InputMode {
+        let mut app_mock = MockApplication::default();
+        app_mock
+            .expect_on_request_replacements()
+            .once()
+            .return_const(pending);
+        let mut input_handler = InputHandler::default();
+        handle(&mut input_handler, KeyCode::Char('R'), &mut app_mock);
+        input_handler.input_mode
+    }
+
+    #[test]
+    fn apply_replacements() {
+        let mut app_mock = MockApplication::default();
+        app_mock
+            .expect_on_apply_replacements()
+            .once()
+            .return_const(());
+        let mut input_handler = InputHandler {
+            input_mode: InputMode::ConfirmReplace,
+            ..Default::default()
+        };
+        input_handler.handle_key_in_confirm_replace_mode(
+            KeyEvent::new(KeyCode::Char('y'), KeyModifiers::NONE),
+            &mut app_mock,
+        );
+        assert_eq!(input_handler.input_mode, InputMode::Normal);
+    }
+
+    #[test_case(KeyEvent::new(KeyCode::Char('n'), KeyModifiers::NONE))]
+    #[test_case(KeyEvent::new(KeyCode::Char('R'), KeyModifiers::SHIFT))]
+    #[test_case(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE))]
+    fn cancel_replacements(event: KeyEvent) {
+        let mut app_mock = MockApplication::default();
+        app_mock
+            .expect_on_cancel_replacements()
+            .once()
+            .return_const(());
+        let mut input_handler = InputHandler {
+            input_mode: InputMode::ConfirmReplace,
+            ..Default::default()
+        };
+        input_handler.handle_key_in_confirm_replace_mode(event, &mut app_mock);
+        assert_eq!(input_handler.input_mode, InputMode::Normal);
+    }
+
+    #[test]
+    fn export_patch() {
+        let mut app_mock = MockApplication::default();
+        app_mock.expect_on_export_patch().once().return_const(());
+        handle_key(KeyCode::Char('P'), &mut app_mock);
+    }
+
     #[test_case(KeyCode::F(1))]
     #[test_case(KeyCode::Char('?'))]
     fn keymap_open(key_code: KeyCode) {
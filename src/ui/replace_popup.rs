@@ -0,0 +1,105 @@
+use ratatui::{
+    backend::CrosstermBackend,
+    layout::Alignment,
+    text::{Line, Span, Text},
+    widgets::{Block, Borders, Clear, Paragraph},
+    Frame,
+};
+
+use super::{search_popup::SearchPopup, theme::Theme};
+
+/// Popup editing the replacement of matches, previewed in the result list.
+#[derive(Default)]
+pub struct ReplacePopup {
+    visible: bool,
+    template: String,
+    error: Option<String>,
+}
+
+impl ReplacePopup {
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+        self.error = None;
+    }
+
+    /// Shows why matches cannot be replaced, until the next edit.
+    pub fn set_error(&mut self, error: String) {
+        self.error = Some(error);
+    }
+
+    pub fn set_template(&mut self, template: String) {
+        self.template = template;
+    }
+
+    pub fn get_template(&self) -> String {
+        self.template.clone()
+    }
+
+    pub fn insert_char(&mut self, c: char) {
+        self.template.push(c);
+        self.error = None;
+    }
+
+    pub fn remove_char(&mut self) {
+        self.template.pop();
+        self.error = None;
+    }
+
+    pub fn draw(&self, frame: &mut Frame<CrosstermBackend<std::io::Stdout>>, theme: &dyn Theme) {
+        if !self.visible {
+            return;
+        }
+
+        let error_lines = self
+            .error
+            .as_deref()
+            .map(|error| error.lines().collect::<Vec<_>>())
+            .unwrap_or_default();
+        let popup_area = SearchPopup::get_popup_area(frame.size(), 50, error_lines.len() as u16);
+        frame.render_widget(Clear, popup_area);
+
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .border_style(theme.search_popup_border())
+            .title("替换为( $1 ${name}, Ctrl+d 关闭替换)")
+            .title_alignment(Alignment::Center);
+        frame.render_widget(block, popup_area);
+
+        let mut text_area = popup_area;
+        text_area.y += 1; // one line below the border
+        text_area.x += 2; // two chars to the right
+        text_area.height = 1;
+
+        let max_text_width = text_area.width as usize - 4;
+        let template = if self.template.len() > max_text_width {
+            format!(
+                "…{}",
+                &self.template[self.template.len() - max_text_width + 1..]
+            )
+        } else {
+            self.template.clone()
+        };
+        frame.render_widget(Paragraph::new(template.as_str()), text_area);
+
+        if !error_lines.is_empty() {
+            let mut error_area = text_area;
+            error_area.y += 1;
+            error_area.height = error_lines.len() as u16;
+            error_area.width = error_area.width.saturating_sub(4);
+            let error_text = Text::from(
+                error_lines
+                    .into_iter()
+                    .map(|line| Line::from(Span::styled(line, theme.diagnostic_color())))
+                    .collect::<Vec<_>>(),
+            );
+            frame.render_widget(Paragraph::new(error_text), error_area);
+        }
+        frame.set_cursor(
+            std::cmp::min(
+                text_area.x + template.len() as u16,
+                text_area.x + text_area.width - 4,
+            ),
+            text_area.y,
+        );
+    }
+}
@@ -1,76 +1,294 @@
-use regex::Regex;
-
-use std::cmp;
+use std::ops::Range;
 
 use ratatui::{
     backend::CrosstermBackend,
     layout::Rect,
     style::Style,
-    text::{Line, Span },
+    text::{Line, Span, Text},
     widgets::{Block, BorderType, Borders},
     Frame,
 };
 
-use crate::ig::file_entry::{EntryType, FileEntry};
+use crate::{
+    editor::Column,
+    ig::{
+        file_entry::{FileEntry, FileHeader},
+        Replacer, SearchedLine,
+    },
+};
 
 use super::{
+    entry_store::{Entry, EntryStore, MatchSpan, Row},
     scroll_offset_list::{List, ListItem, ListState, ScrollOffset},
     soft_warp::{SoftWrapper, SplitPosType},
+    sort::Sort,
     theme::Theme,
 };
 
+/// File in the list, its rows start with its header.
+#[derive(Clone, Debug)]
+struct FileRows {
+    file: u32,
+    /// Index of the header in the list.
+    start: usize,
+    matches: usize,
+}
+
 #[derive(Default)]
 pub struct ResultList {
-    entries: Vec<EntryType>,
+    entries: EntryStore,
+    /// Files in the order of the list. Rows stay in the store as they were found, so that
+    /// sorting moves only the files.
+    files: Vec<FileRows>,
     state: ListState,
     file_entries_count: usize,
     matches_count: usize,
     filtered_matches_count: usize,
+    sort: Option<Sort>,
 }
 
 impl ResultList {
     pub fn add_entry(&mut self, entry: FileEntry) {
-        self.file_entries_count += 1;
         self.matches_count += entry.get_matches_count();
+        if entry.is_continued() {
+            self.append_to_file(entry);
+        } else {
+            self.file_entries_count += 1;
+            let (file, _) = self.entries.store(entry);
+            let matches = self.rows(file).iter().filter(|row| row.is_match()).count();
+            self.insert_file(file, matches, self.files.len());
+        }
+    }
 
-        self.entries.append(&mut entry.get_entries());
+    /// Inserts a file at the position given by the sort order, or at `position` if results
+    /// are not sorted. Returns the index of its header.
+    fn insert_file(&mut self, file: u32, matches: usize, position: usize) -> usize {
+        let position = match self.sort {
+            Some(sort) => self.sorted_position(sort, file, matches),
+            None => position,
+        };
+        let start = self
+            .files
+            .get(position)
+            .map_or(self.len(), |next| next.start);
+        let inserted = self.rows(file).len();
+        self.shift_files(position, inserted as isize);
+        self.files.insert(
+            position,
+            FileRows {
+                file,
+                start,
+                matches,
+            },
+        );
 
-        if self.state.selected().is_none() {
-            self.next_match();
+        match self.state.selected() {
+            Some(selected) if start <= selected => self.state.select(Some(selected + inserted)),
+            Some(_) => (),
+            None => self.next_match(),
         }
+        start
     }
-    pub fn toggel_text_wrapper(&mut self) {
-        self.state.toggel_wrapper()
+
+    /// Appends next batch of entries to a file already in the list, replacing its header.
+    fn append_to_file(&mut self, entry: FileEntry) {
+        let file = self.entries.find_file(entry.id());
+        let Some(position) =
+            file.and_then(|file| self.files.iter().position(|rows| rows.file == file))
+        else {
+            // the file has been removed from the list in the meantime
+            self.filtered_matches_count += entry.get_matches_count();
+            return;
+        };
+
+        // header is replaced in the store, its row stays the same
+        let (file, appended) = self.entries.store(entry);
+        let matches = self.rows(file)[appended.clone()]
+            .iter()
+            .filter(|row| row.is_match())
+            .count();
+        self.files[position].matches += matches;
+        let end = self.files[position].start + appended.start;
+        self.shift_files(position + 1, appended.len() as isize);
+        if let Some(selected) = self.state.selected().filter(|&index| index >= end) {
+            self.state.select(Some(selected + appended.len()));
+        }
+
+        if let Some(sort) = self.sort {
+            self.keep_sorted(sort, position);
+        }
+    }
+
+    /// Moves the file at `position` to where it belongs after its sort key has changed.
+    /// Files equal to their neighbours stay where they are.
+    fn keep_sorted(&mut self, sort: Sort, position: usize) {
+        let key = self.sort_key(&self.files[position]);
+        let after_previous = position == 0
+            || sort
+                .compare(self.sort_key(&self.files[position - 1]), key)
+                .is_le();
+        let before_next = self
+            .files
+            .get(position + 1)
+            .is_none_or(|next| sort.compare(key, self.sort_key(next)).is_le());
+        if after_previous && before_next {
+            return;
+        }
+
+        let moved = self.files.remove(position);
+        let len = self.rows(moved.file).len();
+        self.shift_files(position, -(len as isize));
+        let selected = self.state.selected();
+        let selected_in_file = selected
+            .filter(|index| (moved.start..moved.start + len).contains(index))
+            .map(|index| index - moved.start);
+        if let Some(selected) = selected.filter(|&index| index >= moved.start + len) {
+            self.state.select(Some(selected - len));
+        }
+
+        let start = self.insert_file(moved.file, moved.matches, position);
+        if let Some(offset) = selected_in_file {
+            self.state.select(Some(start + offset));
+        }
+    }
+
+    /// Removes rows of a single file, together with the file if its header is removed.
+    /// Returns the number of removed matches.
+    fn remove_rows(&mut self, range: Range<usize>) -> usize {
+        let position = self.file_position(range.start);
+        let file = &mut self.files[position];
+        let start = file.start;
+        let matches = self
+            .entries
+            .remove(file.file, range.start - start..range.end - start);
+        let next = if range.start == start {
+            self.files.remove(position);
+            position
+        } else {
+            file.matches -= matches;
+            position + 1
+        };
+        self.shift_files(next, -(range.len() as isize));
+        matches
     }
 
-    pub fn entries(&self) -> &Vec<EntryType> {
-        self.entries.as_ref()
+    /// Moves headers of files starting at `position` by `delta` rows.
+    fn shift_files(&mut self, position: usize, delta: isize) {
+        for file in &mut self.files[position..] {
+            file.start = file.start.wrapping_add_signed(delta);
+        }
     }
 
-    pub fn iter(&self) -> std::slice::Iter<EntryType> {
-        self.entries.iter()
+    /// Removes all results, sort order is kept for the next search.
+    pub fn clear(&mut self) {
+        *self = Self {
+            sort: self.sort,
+            ..Default::default()
+        };
+    }
+
+    pub fn sort(&self) -> Option<Sort> {
+        self.sort
+    }
+
+    /// Sets the order of files and re-sorts already collected results.
+    /// `None` keeps the current order, new files are appended at the end.
+    pub fn sort_by(&mut self, sort: Option<Sort>) {
+        self.sort = sort;
+        let Some(sort) = sort else {
+            return;
+        };
+
+        let selected = self.state.selected().map(|index| {
+            let file = &self.files[self.file_position(index)];
+            (file.file, index - file.start)
+        });
+        let mut files = std::mem::take(&mut self.files);
+        files.sort_by(|lhs, rhs| sort.compare(self.sort_key(lhs), self.sort_key(rhs)));
+
+        let mut new_selected = None;
+        let mut start = 0;
+        for file in &mut files {
+            file.start = start;
+            if let Some((_, offset)) = selected.filter(|&(selected, _)| selected == file.file) {
+                new_selected = Some(start + offset);
+            }
+            start += self.rows(file.file).len();
+        }
+        self.files = files;
+
+        self.state.select(None);
+        match new_selected {
+            Some(index) => self.jump_to(index),
+            None => self.next_match(),
+        }
+    }
+
+    /// Position among files of a file with `matches` given by the sort order, after files
+    /// equal to it.
+    fn sorted_position(&self, sort: Sort, file: u32, matches: usize) -> usize {
+        let key = (self.entries.file(file), matches);
+        self.files
+            .partition_point(|other| sort.compare(self.sort_key(other), key).is_le())
+    }
+
+    fn sort_key(&self, file: &FileRows) -> (&FileHeader, usize) {
+        (self.entries.file(file.file), file.matches)
+    }
+
+    fn rows(&self, file: u32) -> &[Row] {
+        self.entries.rows(file)
+    }
+
+    /// Number of rows in the list.
+    fn len(&self) -> usize {
+        self.files
+            .last()
+            .map_or(0, |file| file.start + self.rows(file.file).len())
+    }
+
+    /// Position among files of the file with the row at `index`.
+    fn file_position(&self, index: usize) -> usize {
+        self.files
+            .partition_point(|file| file.start <= index)
+            .saturating_sub(1)
+    }
+
+    /// File and row at `index` of the list.
+    fn row(&self, index: usize) -> (u32, Row) {
+        let file = &self.files[self.file_position(index)];
+        (file.file, self.rows(file.file)[index - file.start])
+    }
+
+    fn entry(&self, index: usize) -> Entry<'_> {
+        let (file, row) = self.row(index);
+        self.entries.get(file, row)
+    }
+
+    pub fn toggel_text_wrapper(&mut self) {
+        self.state.toggel_wrapper()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Entry<'_>> {
+        self.files.iter().flat_map(|file| {
+            self.rows(file.file)
+                .iter()
+                .map(|&row| self.entries.get(file.file, row))
+        })
     }
 
     pub fn is_empty(&self) -> bool {
-        self.entries.is_empty()
+        self.files.is_empty()
     }
 
     pub fn jump_to(&mut self, line: usize) {
-        if self.is_empty() {
+        let Some(jump_line) = self.nearest_match_from(line) else {
             return;
-        }
-        let max = self.entries.len();
-        let current = self.state.selected().unwrap_or(0);
-        let jump_line = if line < max {
-            match self.entries[line] {
-                EntryType::Header(_) => line + 1,
-                EntryType::Match(_, _, _) => line,
-            }
-        } else {
-            max
         };
+        let max = self.len();
+        let current = self.state.selected().unwrap_or(0);
         if max - jump_line < 100 {
-            self.state.offset(max - 100);
+            self.state.offset(max.saturating_sub(100));
         } else if jump_line.abs_diff(current) > 100 {
             self.state.offset(jump_line);
         }
@@ -82,29 +300,14 @@ impl ResultList {
             return;
         }
         let current = self.state.selected().unwrap_or(0);
-        let max = self.entries.len();
-        let index = match self.state.selected() {
-            Some(i) => {
-                let current = i as i32;
-                let max = self.entries.len() - 1;
-                if (current + delta) > max as i32 {
-                    max
-                } else if (current + delta) < 1 {
-                    1
-                } else {
-                    let jump_to = delta + i as i32;
-                    let jump_real = match self.entries[jump_to as usize] {
-                        EntryType::Header(_) => jump_to + 1,
-                        EntryType::Match(_, _, _) => jump_to,
-                    };
-                    jump_real as usize
-                }
-            }
-            None => 1,
+        let max = self.len();
+        let target = (current as i64 + delta as i64).clamp(0, max as i64 - 1) as usize;
+        let Some(index) = self.nearest_match_from(target) else {
+            return;
         };
 
         if max - index < 100 {
-            self.state.offset(max - 100);
+            self.state.offset(max.saturating_sub(100));
         } else if index.abs_diff(current) > 100 {
             self.state.offset(index);
         }
@@ -117,17 +320,11 @@ impl ResultList {
         }
 
         let index = match self.state.selected() {
-            Some(i) => {
-                if i == self.entries.len() - 1 {
-                    i
-                } else {
-                    match self.entries[i + 1] {
-                        EntryType::Header(_) => i + 2,
-                        EntryType::Match(_, _, _) => i + 1,
-                    }
-                }
-            }
-            None => 1,
+            Some(i) => self.next_match_index(i).unwrap_or(i),
+            None => match self.first_match_index() {
+                Some(first) => first,
+                None => return,
+            },
         };
 
         self.state.select(Some(index));
@@ -139,17 +336,11 @@ impl ResultList {
         }
 
         let index = match self.state.selected() {
-            Some(i) => {
-                if i == 1 {
-                    1
-                } else {
-                    match self.entries[i - 1] {
-                        EntryType::Header(_) => i - 2,
-                        EntryType::Match(_, _, _) => i - 1,
-                    }
-                }
-            }
-            None => 1,
+            Some(i) => self.previous_match_index(i).unwrap_or(i),
+            None => match self.first_match_index() {
+                Some(first) => first,
+                None => return,
+            },
         };
 
         self.state.select(Some(index));
@@ -161,26 +352,14 @@ impl ResultList {
         }
 
         let index = match self.state.selected() {
-            Some(i) => {
-                let mut next_index = i;
-                loop {
-                    if next_index == self.entries.len() - 1 {
-                        next_index = i;
-                        break;
-                    }
-
-                    next_index += 1;
-                    match self.entries[next_index] {
-                        EntryType::Header(_) => {
-                            next_index += 1;
-                            break;
-                        }
-                        EntryType::Match(_, _, _) => continue,
-                    }
-                }
-                next_index
-            }
-            None => 1,
+            Some(i) => (i + 1..self.len())
+                .find(|&index| self.is_header(index))
+                .and_then(|header| self.next_match_index(header))
+                .unwrap_or(i),
+            None => match self.first_match_index() {
+                Some(first) => first,
+                None => return,
+            },
         };
 
         self.state.select(Some(index));
@@ -193,50 +372,35 @@ impl ResultList {
 
         let index = match self.state.selected() {
             Some(i) => {
-                let mut next_index = i;
-                let mut first_header_visited = false;
-                loop {
-                    if next_index == 1 {
-                        break;
-                    }
-
-                    next_index -= 1;
-                    match self.entries[next_index] {
-                        EntryType::Header(_) => {
-                            if !first_header_visited {
-                                first_header_visited = true;
-                                next_index -= 1;
-                            } else {
-                                next_index += 1;
-                                break;
-                            }
-                        }
-                        EntryType::Match(_, _, _) => continue,
-                    }
-                }
-                next_index
+                let current_header = self.current_header_index(i);
+                (0..current_header)
+                    .rev()
+                    .find(|&index| self.is_header(index))
+                    .and_then(|header| self.next_match_index(header))
+                    .or_else(|| self.first_match_index())
+                    .unwrap_or(i)
             }
-            None => 1,
+            None => match self.first_match_index() {
+                Some(first) => first,
+                None => return,
+            },
         };
 
         self.state.select(Some(index));
     }
 
     pub fn top(&mut self) {
-        if self.is_empty() {
-            return;
+        if let Some(first) = self.first_match_index() {
+            self.state.select(Some(first));
+            self.state.offset(0);
         }
-        self.state.select(Some(1));
-        self.state.offset(0);
     }
 
     pub fn bottom(&mut self) {
-        if self.is_empty() {
-            return;
+        if let Some(last) = self.last_match_index() {
+            self.state.select(Some(last));
+            self.state.offset(self.len().saturating_sub(100));
         }
-
-        self.state.select(Some(self.entries.len() - 1));
-        self.state.offset(self.entries.len() - 100);
     }
 
     pub fn remove_current_entry(&mut self) {
@@ -257,97 +421,105 @@ impl ResultList {
         }
 
         let selected_index = self.state.selected().expect("Nothing selected");
+        let current_file_header_index = self.current_header_index(selected_index);
+        let next_file_header_index = (selected_index..self.len())
+            .find(|&index| self.is_header(index))
+            .unwrap_or(self.len());
+
+        self.filtered_matches_count +=
+            self.remove_rows(current_file_header_index..next_file_header_index);
+
+        let index = self
+            .previous_match_index(current_file_header_index)
+            .or_else(|| self.first_match_index());
+        self.state.select(index);
+    }
 
-        let mut current_file_header_index = 0;
-        for index in (0..selected_index).rev() {
-            if self.is_header(index) {
-                current_file_header_index = index;
-                break;
-            }
-        }
+    fn is_header(&self, index: usize) -> bool {
+        self.row(index).1.is_header()
+    }
 
-        let mut next_file_header_index = self.entries.len();
-        for index in selected_index..self.entries.len() {
-            if self.is_header(index) {
-                next_file_header_index = index;
-                break;
-            }
-        }
+    fn is_match(&self, index: usize) -> bool {
+        self.row(index).1.is_match()
+    }
 
-        let span = next_file_header_index - current_file_header_index;
-        for _ in 0..span {
-            self.entries.remove(current_file_header_index);
-        }
+    fn first_match_index(&self) -> Option<usize> {
+        (0..self.len()).find(|&index| self.is_match(index))
+    }
 
-        self.filtered_matches_count += span - 1;
+    fn last_match_index(&self) -> Option<usize> {
+        (0..self.len()).rev().find(|&index| self.is_match(index))
+    }
 
-        if self.entries.is_empty() {
-            self.state.select(None);
-        } else if selected_index != 1 {
-            self.state.select(Some(cmp::max(
-                current_file_header_index.saturating_sub(1),
-                1,
-            )));
-        }
+    fn next_match_index(&self, index: usize) -> Option<usize> {
+        (index + 1..self.len()).find(|&index| self.is_match(index))
     }
 
-    fn is_header(&self, index: usize) -> bool {
-        matches!(self.entries[index], EntryType::Header(_))
+    fn previous_match_index(&self, index: usize) -> Option<usize> {
+        (0..index).rev().find(|&index| self.is_match(index))
+    }
+
+    /// Returns the first match at or after `index`, falling back to the last match before it.
+    fn nearest_match_from(&self, index: usize) -> Option<usize> {
+        (index..self.len())
+            .find(|&index| self.is_match(index))
+            .or_else(|| self.previous_match_index(index.min(self.len())))
+    }
+
+    fn current_header_index(&self, index: usize) -> usize {
+        self.files
+            .get(self.file_position(index))
+            .map_or(0, |file| file.start)
     }
 
     fn is_last_match_in_file(&self) -> bool {
         let current_index = self.state.selected().expect("Nothing selected");
+        let current_file_header_index = self.current_header_index(current_index);
 
-        self.is_header(current_index - 1)
-            && (current_index == self.entries.len() - 1 || self.is_header(current_index + 1))
+        !(current_file_header_index + 1..self.len())
+            .take_while(|&index| !self.is_header(index))
+            .any(|index| index != current_index && self.is_match(index))
     }
 
     fn remove_current_entry_and_select_previous(&mut self) {
         let selected_index = self.state.selected().expect("Nothing selected");
-        self.entries.remove(selected_index);
-        self.filtered_matches_count += 1;
+        self.filtered_matches_count += self.remove_rows(selected_index..selected_index + 1);
 
-        if selected_index >= self.entries.len() || self.is_header(selected_index) {
-            self.state.select(Some(selected_index - 1));
-        }
+        let same_file_match = (selected_index..self.len())
+            .take_while(|&index| !self.is_header(index))
+            .find(|&index| self.is_match(index));
+        let index = same_file_match.or_else(|| self.previous_match_index(selected_index));
+        self.state.select(index);
     }
 
     pub fn get_selected_entry(&self) -> Option<(String, u64)> {
-        let re = Regex::new("^\\d").unwrap();
-        match self.state.selected() {
-            Some(i) => {
-                let mut line_number: Option<u64> = None;
-                for index in (0..=i).rev() {
-                    match &self.entries[index] {
-                        EntryType::Header(name) => {
-                            if !name.starts_with("----") {
-                                return Some((
-                                    name.to_owned(),
-                                    line_number.expect("Line number not specified"),
-                                ));
-                            }
-                        }
-                        EntryType::Match(number, row_text, _) => {
-                            if re.is_match(row_text) || line_number.is_none() {
-                                line_number = Some(*number);
-                            }
-                        }
-                    }
-                }
-                None
-            }
-            None => None,
-        }
+        let (file, row) = self.row(self.state.selected()?);
+        let line_number = row.line_number()?;
+        Some((self.entries.file(file).name.to_owned(), line_number))
+    }
+
+    /// Column of the first match in the selected line, where an editor puts the cursor.
+    pub fn get_selected_column(&self) -> Column {
+        self.state
+            .selected()
+            .and_then(|selected| self.entries.match_column(self.row(selected).1))
+            .unwrap_or_default()
     }
 
     pub fn get_current_match_index(&self) -> usize {
         match self.state.selected() {
             Some(selected) => {
-                self.entries
+                let position = self.file_position(selected);
+                let file = &self.files[position];
+                let in_file = self.rows(file.file)[..selected - file.start]
                     .iter()
-                    .take(selected)
-                    .filter(|&e| matches!(e, EntryType::Match(_, _, _)))
-                    .count()
+                    .filter(|row| row.is_match())
+                    .count();
+                self.files[..position]
+                    .iter()
+                    .map(|file| file.matches)
+                    .sum::<usize>()
+                    + in_file
                     + 1
             }
             None => 0,
@@ -355,10 +527,7 @@ impl ResultList {
     }
 
     pub fn get_current_number_of_matches(&self) -> usize {
-        self.entries
-            .iter()
-            .filter(|&e| matches!(e, EntryType::Match(_, _, _)))
-            .count()
+        self.matches_count - self.filtered_matches_count
     }
 
     pub fn get_total_number_of_matches(&self) -> usize {
@@ -373,101 +542,130 @@ impl ResultList {
         self.filtered_matches_count
     }
 
+    /// Matched lines left in the list, by file. Binary matches are skipped, they cannot
+    /// be replaced.
+    pub fn matched_lines(&self) -> Vec<(String, Vec<SearchedLine>)> {
+        let mut files: Vec<(String, Vec<SearchedLine>)> = Vec::new();
+        let rows = self
+            .files
+            .iter()
+            .flat_map(|file| self.rows(file.file).iter().map(|&row| (file.file, row)));
+        for (file, row) in rows {
+            match self.entries.get(file, row) {
+                Entry::Header(header) => files.push((header.name.clone(), Vec::new())),
+                Entry::Match(number, text, spans) => {
+                    let shortened = self.entries.is_shortened(row);
+                    // only a note is shown for omitted lines, previews have the first match
+                    let text = match (shortened, spans.is_empty()) {
+                        (false, _) => text,
+                        (true, true) => "",
+                        (true, false) => text.trim_matches('…'),
+                    };
+                    if let Some((_, lines)) = files.last_mut() {
+                        lines.push(SearchedLine {
+                            number,
+                            text: text.into(),
+                            shortened,
+                        });
+                    }
+                }
+                _ => (),
+            }
+        }
+        files.retain(|(_, lines)| !lines.is_empty());
+        for (_, lines) in &mut files {
+            lines.sort_unstable_by_key(|line| line.number);
+            lines.dedup_by_key(|line| line.number);
+        }
+        files
+    }
+
     pub fn draw(
         &mut self,
         frame: &mut Frame<CrosstermBackend<std::io::Stdout>>,
         area: Rect,
         theme: &dyn Theme,
+        replacer: Option<&Replacer>,
     ) {
         let mut files_list: Vec<ListItem> = Vec::new();
         let skip = self.state.get_offset();
-        let end = self.entries.len().min(skip + 60);
-
-        for e in &self.entries[skip..end] {
-            match e {
-                EntryType::Header(h) => {
-                    let h = h.trim_start_matches("./");
-                    files_list.push(ListItem::new(Span::styled(h, theme.file_path_color())));
-                }
-                EntryType::Match(n, t, offsets) => {
-                    if self.state.is_wrapper() {
-                        let line_number =
-                            Span::styled(format!(" {n}: "), theme.line_number_color());
-                        let max_width = area.width as usize;
-                        let mut current_position = 0;
-                        let soft_wrapper = SoftWrapper::new(max_width, offsets, t);
-                        let mut match_flag = false;
-                        let mut spans = vec![line_number];
-
-                        for split_pos in soft_wrapper.positions {
-                            let sty = if match_flag {
-                                theme.match_color()
-                            } else {
-                                theme.list_font_color()
-                            };
-                            match split_pos {
-                                SplitPosType::Crlf(x) => {
-                                    let newline_span = Span::styled(&t[current_position..x], sty);
-                                    spans.push(newline_span);
-                                    files_list.push(ListItem::new(Line::from(spans.clone())));
-                                    spans.clear();
-                                    current_position = x;
-                                }
-                                SplitPosType::MatchStart(x) => {
-                                    let before_match = Span::styled(&t[current_position..x], sty);
-                                    spans.push(before_match);
-                                    current_position = x;
-                                    match_flag = true;
-                                }
-                                SplitPosType::MatchEnd(x) => {
-                                    let actual_match_line =
-                                        Span::styled(&t[current_position..x], sty);
-                                    spans.push(actual_match_line);
-                                    current_position = x;
-                                    match_flag = false;
-                                }
-                            }
-                        }
-                    } else {
-                        let line_number =
-                            Span::styled(format!(" {n}: "), theme.line_number_color());
-                        let mut spans = vec![line_number];
-
-                        let mut current_position = 0;
-
-                        for offset in offsets {
-                            let before_match = Span::styled(
-                                &t[current_position..offset.0],
-                                theme.list_font_color(),
-                            );
-                            let actual_match =
-                                Span::styled(&t[offset.0..offset.1], theme.match_color());
-
-                            // set current position to the end of current match
-                            current_position = offset.1;
-
-                            spans.push(before_match);
-                            spans.push(actual_match);
-                        }
-
-                        // push remaining text of a line
+        let end = self.len().min(skip + 60);
+
+        // texts are looked up only for rows in the visible window
+        for index in skip..end {
+            match self.entry(index) {
+                Entry::Header(header) => {
+                    let h = header.name.trim_start_matches("./");
+                    let mut spans = vec![Span::styled(h, theme.file_path_color())];
+                    if header.more_matches > 0 {
                         spans.push(Span::styled(
-                            &t[current_position..],
-                            theme.list_font_color(),
+                            format!(" … {} more matches", header.more_matches),
+                            theme.context_line_color(),
                         ));
-
-                        files_list.push(ListItem::new(Line::from(spans)));
                     }
+                    files_list.push(ListItem::new(Line::from(spans)));
+                }
+                Entry::Match(n, t, offsets) => {
+                    let mut lines = self.line_item(
+                        (n, ':', theme.line_number_color()),
+                        t,
+                        offsets,
+                        theme.list_font_color(),
+                        area.width as usize,
+                        theme,
+                    );
+                    // preview of the line after replacing, aligned with its text
+                    if let Some(replaced) = replacer.and_then(|replacer| replacer.replace_line(t)) {
+                        lines.push(Line::from(vec![
+                            Span::styled(
+                                format!(" {:>width$}→ ", "", width = n.to_string().len()),
+                                theme.line_number_color(),
+                            ),
+                            Span::styled(replaced, theme.replacement_color()),
+                        ]));
+                    }
+                    files_list.push(ListItem::new(Text::from(lines)));
+                }
+                Entry::Context(n, t) => {
+                    files_list.push(ListItem::new(Text::from(self.line_item(
+                        (n, '-', theme.context_line_color()),
+                        t,
+                        &[],
+                        theme.context_line_color(),
+                        area.width as usize,
+                        theme,
+                    ))));
+                }
+                Entry::ContextBreak => {
+                    files_list.push(ListItem::new(Span::styled(
+                        " --",
+                        theme.context_line_color(),
+                    )));
+                }
+                Entry::BinaryMatch(n, offset) => {
+                    files_list.push(ListItem::new(Line::from(vec![
+                        Span::styled(format!(" {n}: "), theme.line_number_color()),
+                        Span::styled(
+                            format!(
+                                "binary file matches (found \"\\0\" byte around offset {offset})"
+                            ),
+                            theme.binary_match_color(),
+                        ),
+                    ])));
                 }
             }
         }
 
+        let mut block = Block::default()
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded);
+        if let Some(sort) = self.sort {
+            let order = if sort.reverse { " ↓" } else { "" };
+            block = block.title(format!(" 排序: {}{order} ", sort.key));
+        }
+
         let list_widget = List::new(files_list)
-            .block(
-                Block::default()
-                    .borders(Borders::ALL)
-                    .border_type(BorderType::Rounded),
-            )
+            .block(block)
             .style(theme.background_color())
             .highlight_style(Style::default().bg(theme.highlight_color()))
             .scroll_offset(ScrollOffset::default().top(1).bottom(0));
@@ -476,11 +674,93 @@ impl ResultList {
         frame.render_stateful_widget(list_widget, area, &mut state);
         self.state = state;
     }
+
+    fn line_item<'a>(
+        &self,
+        (line_number, separator, number_style): (u64, char, Style),
+        t: &'a str,
+        offsets: &[MatchSpan],
+        text_style: Style,
+        max_width: usize,
+        theme: &dyn Theme,
+    ) -> Vec<Line<'a>> {
+        let mut lines = Vec::new();
+        let mut line_start = 0;
+
+        // multiline matches are rendered as one item, each line with its own number
+        for (index, line) in t.split('\n').enumerate() {
+            let line_end = line_start + line.len();
+            let line_offsets = offsets
+                .iter()
+                .filter_map(|&(start, end, pattern)| {
+                    let (start, end, pattern) = (start as usize, end as usize, pattern as usize);
+                    let clipped = (start.max(line_start), end.min(line_end));
+                    let empty_match_in_line =
+                        start == end && (line_start..=line_end).contains(&start);
+                    (clipped.0 < clipped.1 || empty_match_in_line)
+                        .then(|| (clipped.0 - line_start, clipped.1 - line_start, pattern))
+                })
+                .collect::<Vec<_>>();
+            line_start = line_end + 1;
+
+            let number = Span::styled(
+                format!(" {}{separator} ", line_number + index as u64),
+                number_style,
+            );
+            let mut spans = vec![number];
+            let mut current_position = 0;
+
+            if self.state.is_wrapper() {
+                let soft_wrapper = SoftWrapper::new(max_width, &line_offsets, line);
+                let mut sty = text_style;
+
+                for split_pos in soft_wrapper.positions {
+                    let x = split_pos.position();
+                    spans.push(Span::styled(&line[current_position..x], sty));
+                    current_position = x;
+                    match split_pos {
+                        SplitPosType::Crlf(_) => lines.push(Line::from(std::mem::take(&mut spans))),
+                        SplitPosType::MatchStart(_, pattern) => {
+                            sty = theme.pattern_match_color(pattern)
+                        }
+                        SplitPosType::MatchEnd(_) => sty = text_style,
+                    }
+                }
+
+                // soft wrapper yields no positions for an empty line
+                if !spans.is_empty() {
+                    lines.push(Line::from(spans));
+                }
+            } else {
+                for (start, end, pattern) in line_offsets {
+                    let before_match = Span::styled(&line[current_position..start], text_style);
+                    let actual_match =
+                        Span::styled(&line[start..end], theme.pattern_match_color(pattern));
+
+                    // set current position to the end of current match
+                    current_position = end;
+
+                    spans.push(before_match);
+                    spans.push(actual_match);
+                }
+
+                // push remaining text of a line
+                spans.push(Span::styled(&line[current_position..], text_style));
+
+                lines.push(Line::from(spans));
+            }
+        }
+
+        lines
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::ig::grep_match::GrepMatch;
+    use crate::{
+        ig::{file_entry::EntryType, grep_match::GrepMatch},
+        ui::sort::SortKey,
+    };
 
     use super::*;
 
@@ -501,7 +781,7 @@ mod tests {
             "entry1".into(),
             vec![GrepMatch::new(0, "e1m1".into(), vec![])],
         ));
-        assert_eq!(list.entries.len(), 2);
+        assert_eq!(list.len(), 2);
         assert_eq!(list.state.selected(), Some(1));
 
         list.add_entry(FileEntry::new(
@@ -511,7 +791,292 @@ mod tests {
                 GrepMatch::new(0, "e2m2".into(), vec![]),
             ],
         ));
-        assert_eq!(list.entries.len(), 5);
+        assert_eq!(list.len(), 5);
         assert_eq!(list.state.selected(), Some(1));
     }
+
+    fn file_names(list: &ResultList) -> Vec<&str> {
+        list.iter()
+            .filter_map(|e| match e {
+                Entry::Header(header) => Some(header.name.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn entry_with_matches(name: &str, count: u64) -> FileEntry {
+        FileEntry::new(
+            name.into(),
+            (0..count)
+                .map(|n| GrepMatch::new(n, format!("{name}m{n}"), vec![]))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_add_entry_sorted() {
+        let mut list = ResultList::default();
+        list.sort_by(Some(Sort::new(SortKey::Path, false)));
+        list.add_entry(entry_with_matches("b", 1));
+        assert_eq!(list.get_selected_entry(), Some(("b".into(), 0)));
+
+        list.add_entry(entry_with_matches("c", 1));
+        list.add_entry(entry_with_matches("a", 2));
+        assert_eq!(file_names(&list), ["a", "b", "c"]);
+        assert_eq!(list.get_selected_entry(), Some(("b".into(), 0)));
+    }
+
+    #[test]
+    fn test_sort_by() {
+        let mut list = ResultList::default();
+        list.add_entry(entry_with_matches("b", 1));
+        list.add_entry(entry_with_matches("c", 3));
+        list.add_entry(entry_with_matches("a", 2));
+        list.next_file();
+        assert_eq!(list.get_selected_entry(), Some(("c".into(), 0)));
+
+        list.sort_by(Some(Sort::new(SortKey::Matches, true)));
+        assert_eq!(file_names(&list), ["c", "a", "b"]);
+        assert_eq!(list.get_selected_entry(), Some(("c".into(), 0)));
+
+        list.sort_by(Some(Sort::new(SortKey::Path, false)));
+        assert_eq!(file_names(&list), ["a", "b", "c"]);
+        assert_eq!(list.get_selected_entry(), Some(("c".into(), 0)));
+
+        list.clear();
+        assert!(list.is_empty());
+        assert_eq!(list.sort(), Some(Sort::new(SortKey::Path, false)));
+    }
+
+    #[test]
+    fn test_add_continued_entry() {
+        let mut list = ResultList::default();
+        let a = entry_with_matches("a", 1);
+        let a_id = a.id();
+        list.add_entry(a);
+        list.add_entry(entry_with_matches("b", 1));
+        list.next_file();
+        assert_eq!(list.get_selected_entry(), Some(("b".into(), 0)));
+
+        list.add_entry(
+            FileEntry::new("a".into(), vec![GrepMatch::new(7, "am7".into(), vec![])])
+                .more_matches(3)
+                .continued(a_id),
+        );
+        assert_eq!(file_names(&list), ["a", "b"]);
+        assert_eq!(list.len(), 5);
+        assert!(matches!(list.entry(0), Entry::Header(h) if h.more_matches == 3));
+        assert_eq!(list.get_selected_entry(), Some(("b".into(), 0)));
+        assert_eq!(list.get_total_number_of_matches(), 3);
+        assert_eq!(list.get_total_number_of_file_entries(), 2);
+
+        list.top();
+        list.next_match();
+        assert_eq!(list.get_selected_entry(), Some(("a".into(), 7)));
+    }
+
+    #[test]
+    fn test_continued_entry_keeps_sort_order() {
+        let mut list = ResultList::default();
+        list.sort_by(Some(Sort::new(SortKey::Matches, true)));
+        list.add_entry(entry_with_matches("a", 2));
+        let b = entry_with_matches("b", 1);
+        let b_id = b.id();
+        list.add_entry(b);
+        assert_eq!(list.get_selected_entry(), Some(("a".into(), 0)));
+
+        list.add_entry(entry_with_matches("b", 2).continued(b_id));
+        assert_eq!(file_names(&list), ["b", "a"]);
+        assert_eq!(list.get_selected_entry(), Some(("a".into(), 0)));
+    }
+
+    #[test]
+    fn test_continued_entry_after_removing_matches() {
+        let mut list = ResultList::default();
+        let (a, b) = (entry_with_matches("a", 2), entry_with_matches("b", 1));
+        let (a_id, b_id) = (a.id(), b.id());
+        list.add_entry(a);
+        list.add_entry(b);
+        list.remove_current_entry();
+        assert_eq!(list.get_selected_entry(), Some(("a".into(), 1)));
+
+        list.add_entry(
+            FileEntry::new("a".into(), vec![GrepMatch::new(7, "am7".into(), vec![])])
+                .continued(a_id),
+        );
+        list.next_match();
+        assert_eq!(list.get_selected_entry(), Some(("a".into(), 7)));
+        list.next_match();
+        assert_eq!(list.get_selected_entry(), Some(("b".into(), 0)));
+        list.remove_current_file();
+
+        list.add_entry(entry_with_matches("b", 1).continued(b_id));
+        assert_eq!(file_names(&list), ["a"]);
+        assert_eq!(list.len(), 3);
+    }
+
+    #[test]
+    fn test_files_with_same_name_are_kept_apart() {
+        let mut list = ResultList::default();
+        let first = entry_with_matches("<stdin>", 1);
+        let first_id = first.id();
+        list.add_entry(first);
+        list.add_entry(entry_with_matches("<stdin>", 2));
+        list.add_entry(entry_with_matches("<stdin>", 1).continued(first_id));
+
+        assert_eq!(file_names(&list), ["<stdin>", "<stdin>"]);
+        assert_eq!(list.len(), 6);
+        assert_eq!(list.get_total_number_of_file_entries(), 2);
+    }
+
+    #[test]
+    fn test_sorted_files_with_equal_keys_keep_arrival_order() {
+        let mut list = ResultList::default();
+        list.sort_by(Some(Sort::new(SortKey::Path, false)));
+        let first = entry_with_matches("b", 1);
+        let first_id = first.id();
+        list.add_entry(first);
+        list.add_entry(entry_with_matches("b", 2));
+        list.add_entry(entry_with_matches("a", 1));
+        list.add_entry(entry_with_matches("b", 1).continued(first_id));
+
+        assert_eq!(file_names(&list), ["a", "b", "b"]);
+        assert_eq!(list.len(), 8);
+        assert_eq!(list.get_current_match_index(), 2);
+        // the continued entry is appended to the first file named b
+        list.next_match();
+        assert_eq!(list.state.selected(), Some(4));
+        assert_eq!(list.get_current_match_index(), 3);
+        list.next_file();
+        assert_eq!(list.get_current_match_index(), 4);
+    }
+
+    fn list_with_context() -> ResultList {
+        let mut list = ResultList::default();
+        list.add_entry(FileEntry::new(
+            "entry1".into(),
+            vec![
+                EntryType::Context(1, "e1c1".into()),
+                EntryType::Match(2, "e1m1".into(), vec![], None),
+                EntryType::Context(3, "e1c2".into()),
+                EntryType::ContextBreak,
+                EntryType::Context(9, "e1c3".into()),
+                EntryType::Match(10, "e1m2".into(), vec![], None),
+            ],
+        ));
+        list.add_entry(FileEntry::new(
+            "entry2".into(),
+            vec![
+                EntryType::Context(4, "e2c1".into()),
+                EntryType::Match(5, "e2m1".into(), vec![], None),
+            ],
+        ));
+        list
+    }
+
+    #[test]
+    fn test_navigation_skips_context() {
+        let mut list = list_with_context();
+        assert_eq!(list.state.selected(), Some(2));
+
+        list.next_match();
+        assert_eq!(list.state.selected(), Some(6));
+        list.next_match();
+        assert_eq!(list.state.selected(), Some(9));
+        list.next_match();
+        assert_eq!(list.state.selected(), Some(9));
+
+        list.previous_match();
+        assert_eq!(list.state.selected(), Some(6));
+        list.previous_file();
+        assert_eq!(list.state.selected(), Some(2));
+        list.next_file();
+        assert_eq!(list.state.selected(), Some(9));
+
+        list.top();
+        assert_eq!(list.state.selected(), Some(2));
+        list.bottom();
+        assert_eq!(list.state.selected(), Some(9));
+    }
+
+    #[test]
+    fn test_binary_match_is_selectable() {
+        let mut list = ResultList::default();
+        list.add_entry(FileEntry::new(
+            "binary".into(),
+            vec![EntryType::BinaryMatch(3, 120)],
+        ));
+        list.add_entry(FileEntry::new(
+            "text".into(),
+            vec![GrepMatch::new(7, "m".into(), vec![])],
+        ));
+
+        assert_eq!(list.get_total_number_of_matches(), 2);
+        assert_eq!(list.get_selected_entry(), Some(("binary".into(), 3)));
+        list.next_match();
+        assert_eq!(list.get_selected_entry(), Some(("text".into(), 7)));
+    }
+
+    #[test]
+    fn test_selected_entry_with_context() {
+        let mut list = list_with_context();
+        assert_eq!(list.get_selected_entry(), Some(("entry1".into(), 2)));
+        list.next_file();
+        assert_eq!(list.get_selected_entry(), Some(("entry2".into(), 5)));
+        assert_eq!(list.get_current_match_index(), 3);
+        assert_eq!(list.get_current_number_of_matches(), 3);
+    }
+
+    #[test]
+    fn test_remove_with_context() {
+        let mut list = list_with_context();
+        list.remove_current_entry();
+        assert_eq!(list.get_selected_entry(), Some(("entry1".into(), 10)));
+        assert_eq!(list.get_filtered_matches_count(), 1);
+
+        list.remove_current_entry();
+        assert_eq!(list.get_selected_entry(), Some(("entry2".into(), 5)));
+        assert_eq!(list.get_filtered_matches_count(), 2);
+        assert_eq!(list.len(), 3);
+    }
+
+    #[test]
+    fn test_selected_column() {
+        let mut list = ResultList::default();
+        list.add_entry(FileEntry::new(
+            "entry".into(),
+            vec![GrepMatch::new(3, "\tzażółć foo".into(), vec![(12, 15, 0)])],
+        ));
+        assert_eq!(
+            list.get_selected_column(),
+            Column {
+                byte: Some(13),
+                char: 9,
+                display: 16
+            }
+        );
+    }
+
+    #[test]
+    fn test_matched_lines_skip_filtered_out_matches() {
+        let mut list = list_with_context();
+        list.add_entry(FileEntry::new(
+            "binary".into(),
+            vec![EntryType::BinaryMatch(3, 120)],
+        ));
+        list.remove_current_entry();
+
+        let line = |number, text: &str| SearchedLine {
+            number,
+            text: text.into(),
+            shortened: false,
+        };
+        assert_eq!(
+            list.matched_lines(),
+            [
+                ("entry1".into(), vec![line(10, "e1m2")]),
+                ("entry2".into(), vec![line(5, "e2m1")])
+            ]
+        );
+    }
 }
@@ -1,22 +1,72 @@
 use ratatui::{
     backend::CrosstermBackend,
     layout::{Alignment, Constraint, Direction, Layout, Rect},
-    text::{Line, Text},
+    text::{Line, Span, Text},
     widgets::{Block, Borders, Clear, Paragraph},
     Frame,
 };
 
+use std::time::{Duration, Instant};
+
 use super::theme::Theme;
 
+/// Delay between the last edit and the search started in incremental mode.
+const SEARCH_DEBOUNCE: Duration = Duration::from_millis(300);
+
 #[derive(Default)]
 pub struct SearchPopup {
     visible: bool,
     pattern: String,
+    multiline: bool,
+    fixed_strings: bool,
+    line_regexp: bool,
+    invert_match: bool,
+    incremental: bool,
+    search_deadline: Option<Instant>,
+    error: Option<String>,
 }
 
 impl SearchPopup {
     pub fn toggle(&mut self) {
         self.visible = !self.visible;
+        self.search_deadline = None;
+        self.error = None;
+    }
+
+    /// Shows why the pattern cannot be searched for, until the next edit.
+    pub fn set_error(&mut self, error: String) {
+        self.error = Some(error);
+    }
+
+    pub fn has_error(&self) -> bool {
+        self.error.is_some()
+    }
+
+    pub fn is_incremental(&self) -> bool {
+        self.incremental
+    }
+
+    pub fn toggle_incremental(&mut self) {
+        self.incremental = !self.incremental;
+        self.schedule_search();
+    }
+
+    /// Returns true once the debounce delay since the last edit has passed in incremental mode.
+    pub fn take_due_search(&mut self) -> bool {
+        match self.search_deadline {
+            Some(deadline) if deadline <= Instant::now() => {
+                self.search_deadline = None;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn schedule_search(&mut self) {
+        self.error = None;
+        if self.incremental {
+            self.search_deadline = Some(Instant::now() + SEARCH_DEBOUNCE);
+        }
     }
 
     pub fn set_pattern(&mut self, pattern: String) {
@@ -29,13 +79,92 @@ impl SearchPopup {
 
     pub fn insert_char(&mut self, c: char) {
         self.pattern.push(c);
+        self.schedule_search();
     }
 
     pub fn remove_char(&mut self) {
         self.pattern.pop();
+        self.schedule_search();
+    }
+
+    pub fn set_multiline(&mut self, multiline: bool) {
+        self.multiline = multiline;
+    }
+
+    pub fn is_multiline(&self) -> bool {
+        self.multiline
+    }
+
+    pub fn toggle_multiline(&mut self) {
+        self.multiline = !self.multiline;
+        self.schedule_search();
+    }
+
+    pub fn set_fixed_strings(&mut self, fixed_strings: bool) {
+        self.fixed_strings = fixed_strings;
+    }
+
+    pub fn is_fixed_strings(&self) -> bool {
+        self.fixed_strings
+    }
+
+    pub fn toggle_fixed_strings(&mut self) {
+        self.fixed_strings = !self.fixed_strings;
+        self.schedule_search();
+    }
+
+    pub fn set_line_regexp(&mut self, line_regexp: bool) {
+        self.line_regexp = line_regexp;
     }
 
-    pub fn draw(&self, frame: &mut Frame<CrosstermBackend<std::io::Stdout>>, theme: &dyn Theme) {
+    pub fn is_line_regexp(&self) -> bool {
+        self.line_regexp
+    }
+
+    pub fn toggle_line_regexp(&mut self) {
+        self.line_regexp = !self.line_regexp;
+        self.schedule_search();
+    }
+
+    pub fn set_invert_match(&mut self, invert_match: bool) {
+        self.invert_match = invert_match;
+    }
+
+    pub fn is_invert_match(&self) -> bool {
+        self.invert_match
+    }
+
+    pub fn toggle_invert_match(&mut self) {
+        self.invert_match = !self.invert_match;
+        self.schedule_search();
+    }
+
+    fn flags(&self) -> Vec<&'static str> {
+        let mut flags = Vec::new();
+        if self.multiline {
+            flags.push("-U");
+        }
+        if self.fixed_strings {
+            flags.push("-F");
+        }
+        if self.line_regexp {
+            flags.push("-x");
+        }
+        if self.invert_match {
+            flags.push("-v");
+        }
+        if self.incremental {
+            flags.push("live");
+        }
+        flags
+    }
+
+    pub fn draw(
+        &self,
+        frame: &mut Frame<CrosstermBackend<std::io::Stdout>>,
+        live_summary: Option<String>,
+        theme: &dyn Theme,
+    ) {
         if !self.visible {
             return;
         }
@@ -43,16 +172,46 @@ impl SearchPopup {
         let block = Block::default()
             .borders(Borders::ALL)
             .border_style(theme.search_popup_border())
-            .title("搜索条件( pattern glob --a{n}b{n})")
+            .title("搜索条件( pattern|\"p1\" \"p2\" glob --a{n}b{n}m{n}d{n}s{n})")
             .title_alignment(Alignment::Center);
-        let popup_area = Self::get_popup_area(frame.size(), 50);
+        let error_lines = self
+            .error
+            .as_deref()
+            .map(|error| error.lines().collect::<Vec<_>>())
+            .unwrap_or_default();
+        let popup_area = Self::get_popup_area(frame.size(), 50, error_lines.len() as u16);
         frame.render_widget(Clear, popup_area);
 
         frame.render_widget(block, popup_area);
 
+        let flags = self.flags();
+        if !flags.is_empty() {
+            let mut flags_area = popup_area;
+            flags_area.y += popup_area.height - 1; // bottom border
+            flags_area.height = 1;
+            flags_area.width = flags_area.width.saturating_sub(2);
+            let flags_text = Paragraph::new(format!(" {} ", flags.join(" ")))
+                .style(theme.search_popup_border())
+                .alignment(Alignment::Right);
+            frame.render_widget(flags_text, flags_area);
+        }
+
+        if let Some(live_summary) = live_summary {
+            let mut summary_area = popup_area;
+            summary_area.y += popup_area.height - 1; // bottom border
+            summary_area.height = 1;
+            summary_area.x += 2;
+            summary_area.width = summary_area.width.saturating_sub(4);
+            let summary_text = Paragraph::new(format!(" {live_summary} "))
+                .style(theme.search_popup_border())
+                .alignment(Alignment::Left);
+            frame.render_widget(summary_text, summary_area);
+        }
+
         let mut text_area = popup_area;
         text_area.y += 1; // one line below the border
         text_area.x += 2; // two chars to the right
+        text_area.height = 1;
 
         let max_text_width = text_area.width as usize - 4;
         let pattern = if self.pattern.len() > max_text_width {
@@ -67,6 +226,20 @@ impl SearchPopup {
         let text = Text::from(Line::from(pattern.as_str()));
         let pattern_text = Paragraph::new(text);
         frame.render_widget(pattern_text, text_area);
+
+        if !error_lines.is_empty() {
+            let mut error_area = text_area;
+            error_area.y += 1;
+            error_area.height = error_lines.len() as u16;
+            error_area.width = error_area.width.saturating_sub(4);
+            let error_text = Text::from(
+                error_lines
+                    .into_iter()
+                    .map(|line| Line::from(Span::styled(line, theme.diagnostic_color())))
+                    .collect::<Vec<_>>(),
+            );
+            frame.render_widget(Paragraph::new(error_text), error_area);
+        }
         frame.set_cursor(
             std::cmp::min(
                 text_area.x + pattern.len() as u16,
@@ -76,15 +249,15 @@ impl SearchPopup {
         );
     }
 
-    fn get_popup_area(frame_size: Rect, width_percent: u16) -> Rect {
-        const POPUP_HEIGHT: u16 = 3;
-        let top_bottom_margin = (frame_size.height - POPUP_HEIGHT) / 2;
+    pub(super) fn get_popup_area(frame_size: Rect, width_percent: u16, error_height: u16) -> Rect {
+        let popup_height = (3 + error_height).min(frame_size.height);
+        let top_bottom_margin = (frame_size.height - popup_height) / 2;
         let popup_layout = Layout::default()
             .direction(Direction::Vertical)
             .constraints(
                 [
                     Constraint::Length(top_bottom_margin),
-                    Constraint::Length(POPUP_HEIGHT),
+                    Constraint::Length(popup_height),
                     Constraint::Length(top_bottom_margin),
                 ]
                 .as_ref(),
@@ -104,3 +277,23 @@ impl SearchPopup {
             .split(popup_layout[1])[1]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edits_schedule_search_only_in_incremental_mode() {
+        let mut popup = SearchPopup::default();
+        popup.insert_char('a');
+        assert!(popup.search_deadline.is_none());
+
+        popup.toggle_incremental();
+        popup.insert_char('b');
+        assert!(!popup.take_due_search());
+
+        popup.search_deadline = Some(Instant::now());
+        assert!(popup.take_due_search());
+        assert!(!popup.take_due_search());
+    }
+}
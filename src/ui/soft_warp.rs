@@ -1,25 +1,20 @@
 use unicode_width::UnicodeWidthChar;
 
-#[derive(Debug, PartialEq, Eq , Ord)]
+#[derive(Debug, PartialEq, Eq)]
 pub enum SplitPosType {
     Crlf(usize),
-    MatchStart(usize),
+    /// Start of a match, with the index of the pattern that produced it.
+    MatchStart(usize, usize),
     MatchEnd(usize),
 }
 
-impl PartialOrd for SplitPosType {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        let l = match self {
-            SplitPosType::Crlf(x) => x,
-            SplitPosType::MatchStart(x) => x,
-            SplitPosType::MatchEnd(x) => x,
-        };
-        let r = match other {
-            SplitPosType::Crlf(x) => x,
-            SplitPosType::MatchStart(x) => x,
-            SplitPosType::MatchEnd(x) => x,
-        };
-        Some(l.cmp(r))
+impl SplitPosType {
+    pub fn position(&self) -> usize {
+        match self {
+            SplitPosType::Crlf(x) => *x,
+            SplitPosType::MatchStart(x, _) => *x,
+            SplitPosType::MatchEnd(x) => *x,
+        }
     }
 }
 
@@ -28,7 +23,7 @@ pub struct SoftWrapper {
 }
 
 impl SoftWrapper {
-    pub fn new(max_width: usize, matches_offsets: &Vec<(usize, usize)>, text: &str) -> Self {
+    pub fn new(max_width: usize, matches_offsets: &[(usize, usize, usize)], text: &str) -> Self {
         let mut positions = Vec::new();
         if text.is_empty() {
             return Self { positions };
@@ -48,12 +43,13 @@ impl SoftWrapper {
             byte_pos += c.len_utf8();
         }
 
-        for (start, end) in matches_offsets {
-            positions.push(SplitPosType::MatchStart(start.to_owned()));
-            positions.push(SplitPosType::MatchEnd(end.to_owned()));
+        for &(start, end, pattern) in matches_offsets {
+            positions.push(SplitPosType::MatchStart(start, pattern));
+            positions.push(SplitPosType::MatchEnd(end));
         }
         positions.push(SplitPosType::Crlf(text.len()));
-        positions.sort();
+        // stable sort keeps the end of a match before the start of an adjacent one
+        positions.sort_by_key(SplitPosType::position);
 
         Self { positions }
     }
@@ -67,17 +63,18 @@ mod tests {
     fn test_ord() {
         let mut spts = vec![
             SplitPosType::Crlf(4),
-            SplitPosType::MatchStart(2),
+            SplitPosType::MatchStart(2, 0),
             SplitPosType::MatchEnd(3),
             SplitPosType::Crlf(8),
             SplitPosType::Crlf(12),
-            SplitPosType::MatchStart(10),
+            SplitPosType::MatchStart(10, 1),
             SplitPosType::MatchEnd(11),
         ];
 
-        spts.sort();
+        spts.sort_by_key(SplitPosType::position);
 
         println!("{spts:#?}");
+        assert!(spts.windows(2).all(|w| w[0].position() <= w[1].position()));
     }
 
     #[test]
@@ -93,7 +90,7 @@ mod tests {
                     println!("CR|{}", &s[c..x]);
                     c = x;
                 }
-                SplitPosType::MatchStart(x) => {
+                SplitPosType::MatchStart(x, _) => {
                     println!("MS|{}", &s[c..x]);
                     c = x;
                 }
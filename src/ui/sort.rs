@@ -0,0 +1,94 @@
+use std::cmp::Ordering;
+
+use clap::ArgEnum;
+use strum_macros::Display;
+
+use crate::ig::file_entry::FileHeader;
+
+#[derive(Display, PartialEq, Eq, Copy, Clone, Debug, ArgEnum)]
+#[strum(serialize_all = "lowercase")]
+pub enum SortKey {
+    Path,
+    Modified,
+    Accessed,
+    Created,
+    Matches,
+}
+
+impl SortKey {
+    /// Key following this one when cycling through them at runtime, `None` keeps current order.
+    pub fn next(key: Option<SortKey>) -> Option<SortKey> {
+        match key {
+            None => Some(SortKey::Path),
+            Some(SortKey::Path) => Some(SortKey::Modified),
+            Some(SortKey::Modified) => Some(SortKey::Accessed),
+            Some(SortKey::Accessed) => Some(SortKey::Created),
+            Some(SortKey::Created) => Some(SortKey::Matches),
+            Some(SortKey::Matches) => None,
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub struct Sort {
+    pub key: SortKey,
+    pub reverse: bool,
+}
+
+impl Sort {
+    pub fn new(key: SortKey, reverse: bool) -> Self {
+        Self { key, reverse }
+    }
+
+    /// Compares two files given their headers and number of matches.
+    /// Ties are resolved by path, so that the order does not depend on arrival order.
+    pub fn compare(&self, lhs: (&FileHeader, usize), rhs: (&FileHeader, usize)) -> Ordering {
+        let ordering = match self.key {
+            SortKey::Path => Ordering::Equal,
+            SortKey::Modified => lhs.0.modified.cmp(&rhs.0.modified),
+            SortKey::Accessed => lhs.0.accessed.cmp(&rhs.0.accessed),
+            SortKey::Created => lhs.0.created.cmp(&rhs.0.created),
+            SortKey::Matches => lhs.1.cmp(&rhs.1),
+        }
+        .then_with(|| lhs.0.name.cmp(&rhs.0.name));
+
+        if self.reverse {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, SystemTime};
+    use test_case::test_case;
+
+    fn header(name: &str, modified_secs: u64) -> FileHeader {
+        FileHeader {
+            name: name.into(),
+            modified: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(modified_secs)),
+            ..Default::default()
+        }
+    }
+
+    #[test_case(SortKey::Path, false => Ordering::Less)]
+    #[test_case(SortKey::Path, true => Ordering::Greater)]
+    #[test_case(SortKey::Modified, false => Ordering::Greater)]
+    #[test_case(SortKey::Matches, false => Ordering::Less)]
+    #[test_case(SortKey::Matches, true => Ordering::Greater)]
+    fn compare(key: SortKey, reverse: bool) -> Ordering {
+        Sort::new(key, reverse).compare((&header("a", 20), 1), (&header("b", 10), 5))
+    }
+
+    #[test]
+    fn ties_resolved_by_path() {
+        let sort = Sort::new(SortKey::Modified, false);
+        assert_eq!(
+            sort.compare((&header("b", 10), 1), (&header("a", 10), 1)),
+            Ordering::Greater
+        );
+    }
+}
@@ -34,6 +34,35 @@ pub trait Theme {
         Style::default().fg(Color::Red)
     }
 
+    /// Color of a match produced by the pattern with the given index.
+    fn pattern_match_color(&self, pattern: usize) -> Style {
+        const COLORS: [Color; 4] = [
+            Color::Yellow,
+            Color::LightCyan,
+            Color::LightBlue,
+            Color::LightGreen,
+        ];
+        match pattern {
+            0 => self.match_color(),
+            n => Style::default().fg(COLORS[(n - 1) % COLORS.len()]),
+        }
+    }
+
+    fn binary_match_color(&self) -> Style {
+        Style::default()
+            .fg(Color::LightRed)
+            .add_modifier(Modifier::ITALIC)
+    }
+
+    /// Color of a matched line after replacing, previewed below it.
+    fn replacement_color(&self) -> Style {
+        Style::default().fg(Color::LightGreen)
+    }
+
+    fn context_line_color(&self) -> Style {
+        Style::default().add_modifier(Modifier::DIM)
+    }
+
     fn highlight_color(&self) -> Color;
 
     // Context viewer styles
@@ -68,6 +97,13 @@ pub trait Theme {
             .fg(Color::Black)
     }
 
+    fn cancelled_state_style(&self) -> Style {
+        Style::default()
+            .add_modifier(Modifier::BOLD)
+            .bg(Color::Yellow)
+            .fg(Color::Black)
+    }
+
     fn finished_state_style(&self) -> Style {
         Style::default()
             .add_modifier(Modifier::BOLD)
@@ -79,6 +115,10 @@ pub trait Theme {
         Color::Red
     }
 
+    fn diagnostic_color(&self) -> Style {
+        Style::default().fg(Color::Red)
+    }
+
     // Search popup style
     fn search_popup_border(&self) -> Style {
         Style::default().fg(Color::Green)
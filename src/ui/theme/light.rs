@@ -1,5 +1,5 @@
 use super::Theme;
-use ratatui::style::Color;
+use ratatui::style::{Color, Style};
 
 pub struct Light;
 
@@ -8,6 +8,14 @@ impl Theme for Light {
         Color::Rgb(220, 220, 220)
     }
 
+    fn pattern_match_color(&self, pattern: usize) -> Style {
+        const COLORS: [Color; 4] = [Color::Blue, Color::Magenta, Color::Cyan, Color::DarkGray];
+        match pattern {
+            0 => self.match_color(),
+            n => Style::default().fg(COLORS[(n - 1) % COLORS.len()]),
+        }
+    }
+
     fn context_viewer_theme(&self) -> &str {
         "base16-ocean.light"
     }